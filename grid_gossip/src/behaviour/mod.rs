@@ -0,0 +1,72 @@
+use libp2p::{
+    allow_block_list::{ self, BlockedPeers },
+    gossipsub::{ self, AllowAllSubscriptionFilter, IdentityTransform },
+    identify,
+    identity,
+    kad::{ self, store::MemoryStore },
+    mdns,
+    ping,
+    swarm::{ behaviour::toggle::Toggle, NetworkBehaviour },
+    PeerId, StreamProtocol,
+};
+use std::error::Error;
+
+use crate::gossip::init_gossipsub;
+
+/// Kademlia protocol name. Keeps the grid DHT apart from the public IPFS DHT.
+pub const GRID_KAD_PROTOCOL: StreamProtocol = StreamProtocol::new("/zgrid/kad/1.0.0");
+
+/// Protocol family reported through identify.
+pub const GRID_IDENTIFY_PROTOCOL: &str = "/zgrid/id/1.0.0";
+
+/// The composed behaviour every grid node runs. The derive generates
+/// `GridBehaviourEvent` with one variant per field.
+#[derive(NetworkBehaviour)]
+pub struct GridBehaviour {
+    /// Denies connections to and from banned peers.
+    pub blocked_peers: allow_block_list::Behaviour<BlockedPeers>,
+    pub gossipsub: gossipsub::Behaviour<IdentityTransform, AllowAllSubscriptionFilter>,
+    pub mdns: Toggle<mdns::async_io::Behaviour>,
+    pub kad: kad::Behaviour<MemoryStore>,
+    pub identify: identify::Behaviour,
+    pub ping: ping::Behaviour,
+}
+
+impl GridBehaviour {
+    pub fn new(local_node_key: &identity::Keypair, enable_mdns: bool) -> Result<Self, Box<dyn Error>> {
+        let local_node_id = PeerId::from(local_node_key.public());
+
+        let gossipsub = init_gossipsub(local_node_key.clone());
+        let mdns = match enable_mdns {
+            true => Some(mdns::async_io::Behaviour::new(mdns::Config::default(), local_node_id)?),
+            false => None,
+        };
+
+        Ok(GridBehaviour {
+            blocked_peers: allow_block_list::Behaviour::default(),
+            gossipsub,
+            mdns: Toggle::from(mdns),
+            kad: init_kademlia(local_node_id),
+            identify: init_identify(local_node_key),
+            ping: ping::Behaviour::new(ping::Config::new()),
+        })
+    }
+}
+
+
+fn init_kademlia(local_node_id: PeerId) -> kad::Behaviour<MemoryStore> {
+    let mut kad_config = kad::Config::default();
+    kad_config.set_protocol_names(vec![GRID_KAD_PROTOCOL]);
+
+    let mut kademlia = kad::Behaviour::with_config(local_node_id, MemoryStore::new(local_node_id), kad_config);
+    // Grid nodes answer DHT queries even before an external address is confirmed
+    kademlia.set_mode(Some(kad::Mode::Server));
+    kademlia
+}
+
+
+fn init_identify(local_node_key: &identity::Keypair) -> identify::Behaviour {
+    let identify_config = identify::Config::new(GRID_IDENTIFY_PROTOCOL.to_string(), local_node_key.public())
+        .with_agent_version(format!("grid_node/{}", env!("CARGO_PKG_VERSION")));
+    identify::Behaviour::new(identify_config)
+}
@@ -0,0 +1,153 @@
+use clap::{ Args, Parser, Subcommand };
+use grid_gossip::node::NodeConfig;
+use grid_gossip::scoring::ScoringConfig;
+use libp2p::Multiaddr;
+use log::LevelFilter;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A zGRID gossip node. Runs the node unless a subcommand is given.
+#[derive(Debug, Parser)]
+#[command(name = "grid_node", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Protobuf-encoded ed25519 key file holding the node identity.
+    #[arg(long = "key-file", value_name = "PATH", default_value = "grid_node.key", global = true)]
+    pub key_file: PathBuf,
+
+    /// Log level (off, error, warn, info, debug, trace). RUST_LOG overrides it per module.
+    #[arg(long = "log-level", value_name = "LEVEL", default_value = "info", global = true)]
+    pub log_level: LevelFilter,
+
+    #[command(flatten)]
+    pub node: NodeArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Generate a new node key and write it to --key-file.
+    Keygen {
+        /// Replace an existing key file. The node gets a new PeerId.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the PeerId of the key in --key-file.
+    ShowId,
+}
+
+#[derive(Debug, Args)]
+pub struct NodeArgs {
+    /// TCP multiaddr to listen on. Can be repeated.
+    #[arg(long = "tcp-listen", value_name = "MULTIADDR", default_value = "/ip4/0.0.0.0/tcp/0")]
+    pub tcp_listen: Vec<Multiaddr>,
+
+    /// QUIC multiaddr to listen on. Can be repeated.
+    #[arg(long = "quic-listen", value_name = "MULTIADDR", default_value = "/ip4/0.0.0.0/udp/0/quic-v1")]
+    pub quic_listen: Vec<Multiaddr>,
+
+    /// Publicly reachable multiaddr of this node, announced to peers. Can be repeated.
+    #[arg(long = "external-addr", value_name = "MULTIADDR")]
+    pub external_addrs: Vec<Multiaddr>,
+
+    /// Peer to dial on startup, e.g. /ip4/10.0.0.5/tcp/3330/p2p/<peer-id>. The
+    /// /p2p suffix also seeds the Kademlia routing table. Can be repeated.
+    #[arg(long = "bootstrap", value_name = "MULTIADDR")]
+    pub bootstrap: Vec<Multiaddr>,
+
+    /// Gossipsub topic to subscribe to. Can be repeated.
+    #[arg(long = "topic", value_name = "NAME", default_value = "grid_topic")]
+    pub topics: Vec<String>,
+
+    /// Disable mDNS peer discovery on the local network.
+    #[arg(long = "disable-mdns")]
+    pub disable_mdns: bool,
+
+    /// Seconds between Kademlia routing table refreshes.
+    #[arg(long = "kad-refresh-secs", value_name = "SECS", default_value_t = 300, value_parser = clap::value_parser!(u64).range(1..))]
+    pub kad_refresh_secs: u64,
+
+    /// Seconds between peer table reports in the log. 0 disables them.
+    #[arg(long = "peer-report-secs", value_name = "SECS", default_value_t = 60)]
+    pub peer_report_secs: u64,
+
+    #[command(flatten)]
+    pub scoring: ScoringArgs,
+}
+
+#[derive(Debug, Args)]
+#[command(next_help_heading = "Peer scoring")]
+pub struct ScoringArgs {
+    /// File that persists banned peer ids across restarts.
+    #[arg(long = "ban-list", value_name = "PATH", default_value = "grid_node.bans")]
+    pub ban_list: PathBuf,
+
+    /// Weight of every topic score in the total peer score.
+    #[arg(long = "topic-weight", value_name = "WEIGHT", default_value_t = ScoringConfig::default().topic_weight)]
+    pub topic_weight: f64,
+
+    /// Reward per second a peer spends in the topic mesh.
+    #[arg(long = "time-in-mesh-weight", value_name = "WEIGHT", default_value_t = ScoringConfig::default().time_in_mesh_weight)]
+    pub time_in_mesh_weight: f64,
+
+    /// Reward per message a peer delivers first.
+    #[arg(long = "first-delivery-weight", value_name = "WEIGHT", default_value_t = ScoringConfig::default().first_delivery_weight)]
+    pub first_delivery_weight: f64,
+
+    /// Penalty per rejected message, applied to the squared count. Must be negative.
+    #[arg(long = "invalid-message-weight", value_name = "WEIGHT", allow_negative_numbers = true, default_value_t = ScoringConfig::default().invalid_message_weight)]
+    pub invalid_message_weight: f64,
+
+    /// Below this score no gossip is exchanged with the peer.
+    #[arg(long = "gossip-threshold", value_name = "SCORE", allow_negative_numbers = true, default_value_t = ScoringConfig::default().gossip_threshold)]
+    pub gossip_threshold: f64,
+
+    /// Below this score own messages are not published to the peer.
+    #[arg(long = "publish-threshold", value_name = "SCORE", allow_negative_numbers = true, default_value_t = ScoringConfig::default().publish_threshold)]
+    pub publish_threshold: f64,
+
+    /// Below this score all RPCs from the peer are ignored.
+    #[arg(long = "graylist-threshold", value_name = "SCORE", allow_negative_numbers = true, default_value_t = ScoringConfig::default().graylist_threshold)]
+    pub graylist_threshold: f64,
+
+    /// Below this score the peer is disconnected and banned.
+    #[arg(long = "ban-threshold", value_name = "SCORE", allow_negative_numbers = true, default_value_t = ScoringConfig::default().ban_threshold)]
+    pub ban_threshold: f64,
+
+    /// Seconds between peer score checks.
+    #[arg(long = "score-check-secs", value_name = "SECS", default_value_t = ScoringConfig::default().check_interval.as_secs(), value_parser = clap::value_parser!(u64).range(1..))]
+    pub score_check_secs: u64,
+}
+
+impl ScoringArgs {
+    pub fn scoring_config(&self) -> ScoringConfig {
+        ScoringConfig {
+            topic_weight: self.topic_weight,
+            time_in_mesh_weight: self.time_in_mesh_weight,
+            first_delivery_weight: self.first_delivery_weight,
+            invalid_message_weight: self.invalid_message_weight,
+            gossip_threshold: self.gossip_threshold,
+            publish_threshold: self.publish_threshold,
+            graylist_threshold: self.graylist_threshold,
+            ban_threshold: self.ban_threshold,
+            check_interval: Duration::from_secs(self.score_check_secs),
+        }
+    }
+}
+
+impl NodeArgs {
+    pub fn node_config(&self) -> NodeConfig {
+        NodeConfig {
+            tcp_listen_addrs: self.tcp_listen.clone(),
+            quic_listen_addrs: self.quic_listen.clone(),
+            external_addrs: self.external_addrs.clone(),
+            bootstrap_addrs: self.bootstrap.clone(),
+            topics: self.topics.clone(),
+            enable_mdns: !self.disable_mdns,
+            kad_refresh_interval: Duration::from_secs(self.kad_refresh_secs),
+            scoring: self.scoring.scoring_config(),
+            ban_list_path: Some(self.scoring.ban_list.clone()),
+        }
+    }
+}
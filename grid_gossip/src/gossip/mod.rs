@@ -0,0 +1,18 @@
+use libp2p::{
+    gossipsub::{ AllowAllSubscriptionFilter, Behaviour, ConfigBuilder, IdentityTransform, MessageAuthenticity, ValidationMode },
+    identity,
+};
+
+pub fn init_gossipsub(local_key: identity::Keypair) -> Behaviour<IdentityTransform, AllowAllSubscriptionFilter> {
+    let gossipsub_config = ConfigBuilder::default()
+        .validation_mode(ValidationMode::Strict)
+        // Messages are held until the node's validation pipeline reports on them
+        .validate_messages()
+        .build()
+        .expect("Valid config");
+
+    Behaviour::new(
+        MessageAuthenticity::Signed(local_key),
+        gossipsub_config,
+    ).expect("Valid gossipsub behaviour")
+}
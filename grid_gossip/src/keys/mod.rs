@@ -0,0 +1,154 @@
+use libp2p::identity::Keypair;
+use log::info;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Loads the node keypair from `path`, generating and saving a new ed25519
+/// keypair if the file does not exist yet.
+pub fn load_or_generate(path: &Path) -> Result<Keypair, Box<dyn Error>> {
+    if path.exists() {
+        let keypair = load(path)?;
+        info!("Keys: Loaded node key from {}", path.display());
+        return Ok(keypair);
+    }
+
+    let keypair = generate(path, false)?;
+    info!("Keys: Generated new node key at {}", path.display());
+    Ok(keypair)
+}
+
+
+/// Reads a protobuf-encoded keypair. Only ed25519 keys are accepted.
+pub fn load(path: &Path) -> Result<Keypair, Box<dyn Error>> {
+    let bytes = fs::read(path)
+        .map_err(|e| format!("Keys: Failed to read {}: {}", path.display(), e))?;
+    let keypair = Keypair::from_protobuf_encoding(&bytes)
+        .map_err(|e| format!("Keys: {} is not a valid key file: {}", path.display(), e))?;
+
+    if keypair.clone().try_into_ed25519().is_err() {
+        return Err(format!("Keys: {} does not hold an ed25519 key", path.display()).into());
+    }
+    Ok(keypair)
+}
+
+
+/// Generates a fresh ed25519 keypair and writes it to `path`. Refuses to
+/// replace an existing key file unless `overwrite` is set.
+pub fn generate(path: &Path, overwrite: bool) -> Result<Keypair, Box<dyn Error>> {
+    if path.exists() && !overwrite {
+        return Err(format!("Keys: {} already exists", path.display()).into());
+    }
+
+    let keypair = Keypair::generate_ed25519();
+    save(path, &keypair)?;
+    Ok(keypair)
+}
+
+
+/// Writes the key to a temporary file readable by the owner only, then
+/// renames it over `path`, so a replaced key file never keeps looser
+/// permissions than a new one.
+fn save(path: &Path, keypair: &Keypair) -> Result<(), Box<dyn Error>> {
+    let bytes = keypair.to_protobuf_encoding()?;
+
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    let temp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&temp_path)?;
+    // The mode above only applies to a file this call creates
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::keys::{ generate, load, load_or_generate };
+    use libp2p::PeerId;
+    use std::path::PathBuf;
+
+    fn key_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("grid_gossip_{}_{}.key", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_load_or_generate_is_stable() {
+        let path = key_path("stable");
+
+        let first = load_or_generate(&path).unwrap();
+        let second = load_or_generate(&path).unwrap();
+
+        std::fs::remove_file(&path)
+            .expect("Failed to remove key file.");
+
+        assert_eq!(PeerId::from(first.public()), PeerId::from(second.public()));
+    }
+
+    #[test]
+    fn test_generate_refuses_to_overwrite() {
+        let path = key_path("overwrite");
+
+        let original = generate(&path, false).unwrap();
+        let refused = generate(&path, false);
+        let replaced = generate(&path, true).unwrap();
+        let loaded = load(&path).unwrap();
+
+        std::fs::remove_file(&path)
+            .expect("Failed to remove key file.");
+
+        assert!(refused.is_err());
+        assert_ne!(original.public(), replaced.public());
+        assert_eq!(replaced.public(), loaded.public());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_overwrite_makes_the_key_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = key_path("permissions");
+        std::fs::write(&path, b"old key").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        generate(&path, true).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+
+        std::fs::remove_file(&path)
+            .expect("Failed to remove key file.");
+
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_load_rejects_garbage() {
+        let path = key_path("garbage");
+        std::fs::write(&path, b"not a key").unwrap();
+
+        let result = load(&path);
+
+        std::fs::remove_file(&path)
+            .expect("Failed to remove key file.");
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,8 @@
+pub mod behaviour;
+pub mod gossip;
+pub mod keys;
+pub mod message;
+pub mod node;
+pub mod peers;
+pub mod scoring;
+pub mod validation;
@@ -0,0 +1,86 @@
+mod cli;
+
+use clap::Parser;
+use cli::{ Cli, Command };
+use grid_gossip::{ keys, message::{ Envelope, MessageKind }, node::{ Node, NodeHandle } };
+use libp2p::PeerId;
+use log::{ info, warn };
+use std::time::Duration;
+
+#[async_std::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    env_logger::Builder::new()
+        .filter_level(cli.log_level)
+        .parse_default_env()
+        .init();
+
+    match cli.command {
+        Some(Command::Keygen { force }) => {
+            let keypair = keys::generate(&cli.key_file, force).unwrap_or_else(|e| exit_with(e));
+            println!("Wrote {}", cli.key_file.display());
+            println!("{}", PeerId::from(keypair.public()));
+        },
+        Some(Command::ShowId) => {
+            let keypair = keys::load(&cli.key_file).unwrap_or_else(|e| exit_with(e));
+            println!("{}", PeerId::from(keypair.public()));
+        },
+        None => {
+            let local_node_key = keys::load_or_generate(&cli.key_file).unwrap_or_else(|e| exit_with(e));
+
+            let mut node = Node::new(local_node_key, cli.node.node_config())
+                .unwrap_or_else(|e| exit_with(format!("Failed to initialize node: {}", e)));
+
+            for kind in [MessageKind::Status, MessageKind::Transaction, MessageKind::Block, MessageKind::Job] {
+                node.register_handler(kind, log_message);
+            }
+
+            if cli.node.peer_report_secs > 0 {
+                async_std::task::spawn(report_peers(node.handle(), Duration::from_secs(cli.node.peer_report_secs)));
+            }
+
+            if let Err(e) = node.run().await {
+                exit_with(format!("Node stopped: {}", e));
+            }
+        },
+    }
+}
+
+
+fn log_message(source: &PeerId, envelope: &Envelope) {
+    info!("Message: {:?} from {} ({} bytes, sent at {})",
+        envelope.kind, source, envelope.payload.len(), envelope.timestamp);
+}
+
+
+async fn report_peers(handle: NodeHandle, interval: Duration) {
+    loop {
+        async_std::task::sleep(interval).await;
+
+        let peers = match handle.peers().await {
+            Ok(peers) => peers,
+            Err(e) => {
+                warn!("Peers: {}", e);
+                return;
+            }
+        };
+
+        let connected: Vec<_> = peers.iter().filter(|peer| peer.is_connected()).collect();
+        info!("Peers: {} connected, {} known", connected.len(), peers.len());
+        for peer in connected {
+            info!("Peers:   {} agent={} rtt={:?} connections={} addrs={}",
+                peer.peer_id,
+                peer.agent_version.as_deref().unwrap_or("unknown"),
+                peer.rtt,
+                peer.connections,
+                peer.listen_addrs.len());
+        }
+    }
+}
+
+
+fn exit_with(error: impl std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
+}
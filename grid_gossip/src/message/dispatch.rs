@@ -0,0 +1,81 @@
+use libp2p::PeerId;
+use log::debug;
+use std::collections::HashMap;
+
+use crate::message::{ Envelope, MessageKind };
+
+/// Reacts to envelopes of the kinds it is registered for. `source` is the
+/// original publisher when the message is signed, otherwise the forwarding peer.
+pub trait MessageHandler: Send {
+    fn handle(&mut self, source: &PeerId, envelope: &Envelope);
+}
+
+impl<F> MessageHandler for F
+where
+    F: FnMut(&PeerId, &Envelope) + Send,
+{
+    fn handle(&mut self, source: &PeerId, envelope: &Envelope) {
+        self(source, envelope)
+    }
+}
+
+/// Routes incoming envelopes to the handlers registered for their kind.
+#[derive(Default)]
+pub struct Dispatcher {
+    handlers: HashMap<MessageKind, Vec<Box<dyn MessageHandler>>>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Dispatcher::default()
+    }
+
+
+    pub fn register(&mut self, kind: MessageKind, handler: impl MessageHandler + 'static) {
+        self.handlers.entry(kind).or_default().push(Box::new(handler));
+    }
+
+
+    /// Returns the number of handlers that received the envelope.
+    pub fn dispatch(&mut self, source: &PeerId, envelope: &Envelope) -> usize {
+        match self.handlers.get_mut(&envelope.kind) {
+            Some(handlers) => {
+                for handler in handlers.iter_mut() {
+                    handler.handle(source, envelope);
+                }
+                handlers.len()
+            },
+            None => {
+                debug!("Message: No handler registered for {:?}", envelope.kind);
+                0
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::message::{ Dispatcher, Envelope, MessageKind };
+    use libp2p::PeerId;
+    use std::sync::{ Arc, Mutex };
+
+    #[test]
+    fn test_dispatch_by_kind() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut dispatcher = Dispatcher::new();
+
+        let sink = Arc::clone(&received);
+        dispatcher.register(MessageKind::Job, move |_: &PeerId, envelope: &Envelope| {
+            sink.lock().unwrap().push(envelope.payload.clone());
+        });
+
+        let source = PeerId::random();
+        let job_handlers = dispatcher.dispatch(&source, &Envelope::new(MessageKind::Job, b"job".to_vec()));
+        let block_handlers = dispatcher.dispatch(&source, &Envelope::new(MessageKind::Block, b"block".to_vec()));
+
+        assert_eq!(job_handlers, 1);
+        assert_eq!(block_handlers, 0);
+        assert_eq!(*received.lock().unwrap(), vec![b"job".to_vec()]);
+    }
+}
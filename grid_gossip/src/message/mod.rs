@@ -0,0 +1,143 @@
+mod dispatch;
+mod signed;
+
+pub use dispatch::{ Dispatcher, MessageHandler };
+pub use signed::SignedPayload;
+
+use bincode::Options;
+use serde::{ Deserialize, Serialize };
+use std::fmt;
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+/// Wire format version written into every envelope this node publishes.
+pub const ENVELOPE_VERSION: u16 = 1;
+
+/// Upper bound on an encoded envelope, matching the gossipsub default
+/// `max_transmit_size`.
+pub const MAX_ENVELOPE_SIZE: u64 = 65536;
+
+/// What an envelope carries. The discriminant is part of the wire format,
+/// so new kinds must only ever be appended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MessageKind {
+    Status,
+    Transaction,
+    Block,
+    Job,
+}
+
+/// The application message carried in the data field of a gossipsub message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub version: u16,
+    pub kind: MessageKind,
+    pub payload: Vec<u8>,
+    /// Milliseconds since the unix epoch, set by the publisher.
+    pub timestamp: u64,
+}
+
+#[derive(Debug)]
+pub enum MessageError {
+    Encoding(bincode::Error),
+    UnsupportedVersion(u16),
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageError::Encoding(e) => write!(f, "Message: Invalid envelope encoding: {}", e),
+            MessageError::UnsupportedVersion(version) => write!(f, "Message: Unsupported envelope version {}", version),
+        }
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+impl Envelope {
+    pub fn new(kind: MessageKind, payload: Vec<u8>) -> Self {
+        Envelope {
+            version: ENVELOPE_VERSION,
+            kind,
+            payload,
+            timestamp: unix_time_millis(),
+        }
+    }
+
+
+    pub fn encode(&self) -> Result<Vec<u8>, MessageError> {
+        wire_options().serialize(self).map_err(MessageError::Encoding)
+    }
+
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, MessageError> {
+        let envelope: Envelope = wire_options().deserialize(bytes).map_err(MessageError::Encoding)?;
+        if envelope.version != ENVELOPE_VERSION {
+            return Err(MessageError::UnsupportedVersion(envelope.version));
+        }
+        Ok(envelope)
+    }
+}
+
+
+/// Fixed-width little-endian integers, bounded size and no trailing bytes.
+pub(crate) fn wire_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .with_limit(MAX_ENVELOPE_SIZE)
+        .reject_trailing_bytes()
+}
+
+
+pub fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::message::{ Envelope, MessageError, MessageKind, ENVELOPE_VERSION };
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let envelope = Envelope::new(MessageKind::Transaction, b"three-six-nine".to_vec());
+
+        let bytes = envelope.encode().unwrap();
+        let decoded = Envelope::decode(&bytes).unwrap();
+
+        assert_eq!(decoded, envelope);
+    }
+
+    #[test]
+    fn test_envelope_encoding_is_stable() {
+        let envelope = Envelope {
+            version: ENVELOPE_VERSION,
+            kind: MessageKind::Block,
+            payload: vec![0xab],
+            timestamp: 1,
+        };
+
+        let bytes = envelope.encode().unwrap();
+
+        assert_eq!(bytes, vec![
+            1, 0,                       // version
+            2, 0, 0, 0,                 // kind
+            1, 0, 0, 0, 0, 0, 0, 0,     // payload length
+            0xab,                       // payload
+            1, 0, 0, 0, 0, 0, 0, 0,     // timestamp
+        ]);
+    }
+
+    #[test]
+    fn test_envelope_rejects_unknown_version_and_garbage() {
+        let mut envelope = Envelope::new(MessageKind::Status, Vec::new());
+        envelope.version = ENVELOPE_VERSION + 1;
+        let bytes = envelope.encode().unwrap();
+
+        assert!(matches!(Envelope::decode(&bytes), Err(MessageError::UnsupportedVersion(_))));
+        assert!(matches!(Envelope::decode(b"Hello, Meow!"), Err(MessageError::Encoding(_))));
+    }
+}
@@ -0,0 +1,97 @@
+use bincode::Options;
+use libp2p::identity::{ Keypair, PublicKey, SigningError };
+use serde::{ Deserialize, Serialize };
+
+use crate::message::{ wire_options, MessageError };
+
+/// Prefix mixed into every signature so a payload signature can never be
+/// replayed as a signature over some other grid structure.
+const SIGNING_DOMAIN: &[u8] = b"zgrid:signed-payload:v1:";
+
+/// A payload signed by its author, e.g. a transaction signed by the submitting
+/// account rather than by the node that gossips it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedPayload {
+    /// Protobuf-encoded libp2p public key of the author.
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub body: Vec<u8>,
+}
+
+impl SignedPayload {
+    pub fn sign(keypair: &Keypair, body: Vec<u8>) -> Result<Self, SigningError> {
+        let signature = keypair.sign(&signing_bytes(&body))?;
+        Ok(SignedPayload {
+            public_key: keypair.public().encode_protobuf(),
+            signature,
+            body,
+        })
+    }
+
+
+    pub fn author(&self) -> Option<PublicKey> {
+        PublicKey::try_decode_protobuf(&self.public_key).ok()
+    }
+
+
+    /// True if the author key decodes and its signature covers the body.
+    pub fn verify(&self) -> bool {
+        match self.author() {
+            Some(public_key) => public_key.verify(&signing_bytes(&self.body), &self.signature),
+            None => false,
+        }
+    }
+
+
+    pub fn encode(&self) -> Result<Vec<u8>, MessageError> {
+        wire_options().serialize(self).map_err(MessageError::Encoding)
+    }
+
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, MessageError> {
+        wire_options().deserialize(bytes).map_err(MessageError::Encoding)
+    }
+}
+
+
+fn signing_bytes(body: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(SIGNING_DOMAIN.len() + body.len());
+    bytes.extend_from_slice(SIGNING_DOMAIN);
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::message::SignedPayload;
+    use libp2p::identity::Keypair;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let keypair = Keypair::generate_ed25519();
+
+        let signed = SignedPayload::sign(&keypair, b"transfer 369".to_vec()).unwrap();
+        let decoded = SignedPayload::decode(&signed.encode().unwrap()).unwrap();
+
+        assert!(decoded.verify());
+        assert_eq!(decoded.author(), Some(keypair.public()));
+    }
+
+    #[test]
+    fn test_tampered_payload_fails_verification() {
+        let keypair = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+
+        let mut tampered_body = SignedPayload::sign(&keypair, b"transfer 369".to_vec()).unwrap();
+        tampered_body.body = b"transfer 963".to_vec();
+        let mut swapped_key = SignedPayload::sign(&keypair, b"transfer 369".to_vec()).unwrap();
+        swapped_key.public_key = other.public().encode_protobuf();
+        let mut garbage_key = SignedPayload::sign(&keypair, b"transfer 369".to_vec()).unwrap();
+        garbage_key.public_key = b"Hello, Meow!".to_vec();
+
+        assert!(!tampered_body.verify());
+        assert!(!swapped_key.verify());
+        assert!(!garbage_key.verify());
+    }
+}
@@ -0,0 +1,183 @@
+use futures::channel::{ mpsc, oneshot };
+use futures::SinkExt;
+use libp2p::gossipsub::{ MessageId, PublishError };
+use libp2p::identity::Keypair;
+use libp2p::kad::RecordKey;
+use libp2p::PeerId;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::message::{ Envelope, MessageError, MessageKind, SignedPayload };
+use crate::peers::PeerInfo;
+
+/// Requests sent from a `NodeHandle` to the running node's event loop.
+pub(crate) enum Command {
+    Publish {
+        topic: Option<String>,
+        envelope: Envelope,
+        responder: oneshot::Sender<Result<MessageId, NodeError>>,
+    },
+    StartProviding {
+        key: RecordKey,
+        responder: oneshot::Sender<Result<(), NodeError>>,
+    },
+    StopProviding {
+        key: RecordKey,
+    },
+    GetProviders {
+        key: RecordKey,
+        responder: oneshot::Sender<Result<HashSet<PeerId>, NodeError>>,
+    },
+    Peers {
+        responder: oneshot::Sender<Result<Vec<PeerInfo>, NodeError>>,
+    },
+    Ban {
+        peer_id: PeerId,
+        responder: oneshot::Sender<Result<bool, NodeError>>,
+    },
+    Unban {
+        peer_id: PeerId,
+        responder: oneshot::Sender<Result<bool, NodeError>>,
+    },
+    BannedPeers {
+        responder: oneshot::Sender<Result<Vec<PeerId>, NodeError>>,
+    },
+}
+
+#[derive(Debug)]
+pub enum NodeError {
+    Message(MessageError),
+    Publish(PublishError),
+    UnknownTopic(String),
+    Signing(String),
+    Kademlia(String),
+    BanList(std::io::Error),
+    Stopped,
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeError::Message(e) => write!(f, "{}", e),
+            NodeError::Publish(e) => write!(f, "Node: Publish failed: {}", e),
+            NodeError::UnknownTopic(topic) => write!(f, "Node: Not subscribed to topic {}", topic),
+            NodeError::Signing(e) => write!(f, "Node: Failed to sign payload: {}", e),
+            NodeError::Kademlia(e) => write!(f, "Node: Kademlia query failed: {}", e),
+            NodeError::BanList(e) => write!(f, "Node: Failed to persist ban list: {}", e),
+            NodeError::Stopped => write!(f, "Node: Event loop is not running"),
+        }
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+/// Cloneable handle other grid components use to talk to a running node.
+#[derive(Clone)]
+pub struct NodeHandle {
+    command_sender: mpsc::Sender<Command>,
+    local_node_key: Keypair,
+}
+
+impl NodeHandle {
+    pub(crate) fn new(command_sender: mpsc::Sender<Command>, local_node_key: Keypair) -> Self {
+        NodeHandle { command_sender, local_node_key }
+    }
+
+
+    /// Publishes on the node's default topic, the first one it subscribed to.
+    pub async fn publish(&self, kind: MessageKind, payload: Vec<u8>) -> Result<MessageId, NodeError> {
+        self.send_publish(None, Envelope::new(kind, payload)).await
+    }
+
+
+    /// Publishes on a specific topic. The node must be subscribed to it.
+    pub async fn publish_to(&self, topic: &str, kind: MessageKind, payload: Vec<u8>) -> Result<MessageId, NodeError> {
+        self.send_publish(Some(topic.to_string()), Envelope::new(kind, payload)).await
+    }
+
+
+    /// Wraps `body` in a `SignedPayload` signed with the node key and publishes
+    /// it on the default topic. Required for kinds validated by signature.
+    pub async fn publish_signed(&self, kind: MessageKind, body: Vec<u8>) -> Result<MessageId, NodeError> {
+        let signed = SignedPayload::sign(&self.local_node_key, body)
+            .map_err(|e| NodeError::Signing(e.to_string()))?;
+        let payload = signed.encode().map_err(NodeError::Message)?;
+        self.publish(kind, payload).await
+    }
+
+
+    /// Announces in the DHT that this node holds the content identified by `key`.
+    /// Resolves once the provider record reached the closest peers.
+    pub async fn start_providing(&self, key: &[u8]) -> Result<(), NodeError> {
+        let key = RecordKey::new(&key);
+        self.request(|responder| Command::StartProviding { key, responder }).await
+    }
+
+
+    /// Stops announcing `key`. Records already stored by other peers expire on their own.
+    pub async fn stop_providing(&self, key: &[u8]) -> Result<(), NodeError> {
+        let key = RecordKey::new(&key);
+        self.send(Command::StopProviding { key }).await
+    }
+
+
+    /// Looks up the peers that announced they hold `key`, including this node.
+    pub async fn get_providers(&self, key: &[u8]) -> Result<HashSet<PeerId>, NodeError> {
+        let key = RecordKey::new(&key);
+        self.request(|responder| Command::GetProviders { key, responder }).await
+    }
+
+
+    /// Returns the node's peer table, connected peers first.
+    pub async fn peers(&self) -> Result<Vec<PeerInfo>, NodeError> {
+        self.request(|responder| Command::Peers { responder }).await
+    }
+
+
+    /// Returns the peer table entry for `peer_id`, if the node has seen it.
+    pub async fn peer(&self, peer_id: &PeerId) -> Result<Option<PeerInfo>, NodeError> {
+        let peers = self.peers().await?;
+        Ok(peers.into_iter().find(|peer| &peer.peer_id == peer_id))
+    }
+
+
+    /// Disconnects and blocks `peer_id` and records it in the ban list.
+    /// Returns false if it was already banned.
+    pub async fn ban(&self, peer_id: PeerId) -> Result<bool, NodeError> {
+        self.request(|responder| Command::Ban { peer_id, responder }).await
+    }
+
+
+    /// Lifts a ban. Returns false if the peer was not banned.
+    pub async fn unban(&self, peer_id: PeerId) -> Result<bool, NodeError> {
+        self.request(|responder| Command::Unban { peer_id, responder }).await
+    }
+
+
+    pub async fn banned_peers(&self) -> Result<Vec<PeerId>, NodeError> {
+        self.request(|responder| Command::BannedPeers { responder }).await
+    }
+
+
+    async fn send_publish(&self, topic: Option<String>, envelope: Envelope) -> Result<MessageId, NodeError> {
+        self.request(|responder| Command::Publish { topic, envelope, responder }).await
+    }
+
+
+    async fn request<T>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<Result<T, NodeError>>) -> Command
+    ) -> Result<T, NodeError> {
+        let (responder, response) = oneshot::channel();
+        self.send(command(responder)).await?;
+        response.await.map_err(|_| NodeError::Stopped)?
+    }
+
+
+    async fn send(&self, command: Command) -> Result<(), NodeError> {
+        self.command_sender.clone()
+            .send(command)
+            .await
+            .map_err(|_| NodeError::Stopped)
+    }
+}
@@ -0,0 +1,117 @@
+use futures::channel::oneshot;
+use libp2p::{
+    kad::{ self, GetProvidersOk, QueryId, QueryResult },
+    multiaddr::Protocol,
+    Multiaddr, PeerId,
+};
+use log::{ debug, info, warn };
+use std::collections::HashSet;
+
+use super::{ Node, NodeError };
+
+/// A DHT query started on behalf of a `NodeHandle`, completed when kad reports its last step.
+pub(super) enum PendingQuery {
+    StartProviding(oneshot::Sender<Result<(), NodeError>>),
+    GetProviders {
+        providers: HashSet<PeerId>,
+        responder: oneshot::Sender<Result<HashSet<PeerId>, NodeError>>,
+    },
+}
+
+impl Node {
+    /// Seeds the routing table with every bootstrap address that names its peer
+    /// (`.../p2p/<peer-id>`). Addresses without a peer id are only dialed.
+    pub(super) fn add_bootstrap_peers(&mut self) {
+        for address in self.config.bootstrap_addrs.clone() {
+            if let Some(peer_id) = peer_id_of(&address) {
+                self.swarm.behaviour_mut().kad.add_address(&peer_id, address.clone());
+                debug!("Node:Kad: Added bootstrap peer {} at {}", peer_id, address);
+            }
+        }
+    }
+
+
+    pub(super) fn bootstrap_kademlia(&mut self) {
+        match self.swarm.behaviour_mut().kad.bootstrap() {
+            Ok(query_id) => debug!("Node:Kad: Routing table refresh started ({:?})", query_id),
+            Err(_) => debug!("Node:Kad: No known peers, skipping routing table refresh"),
+        }
+    }
+
+
+    pub(super) fn start_providing(&mut self, key: kad::RecordKey, responder: oneshot::Sender<Result<(), NodeError>>) {
+        match self.swarm.behaviour_mut().kad.start_providing(key) {
+            Ok(query_id) => {
+                self.pending_queries.insert(query_id, PendingQuery::StartProviding(responder));
+            },
+            Err(e) => {
+                let _ = responder.send(Err(NodeError::Kademlia(e.to_string())));
+            },
+        }
+    }
+
+
+    pub(super) fn get_providers(
+        &mut self,
+        key: kad::RecordKey,
+        responder: oneshot::Sender<Result<HashSet<PeerId>, NodeError>>
+    ) {
+        let query_id = self.swarm.behaviour_mut().kad.get_providers(key);
+        self.pending_queries.insert(query_id, PendingQuery::GetProviders { providers: HashSet::new(), responder });
+    }
+
+
+    pub(super) fn handle_kad_event(&mut self, event: kad::Event) {
+        match event {
+            kad::Event::OutboundQueryProgressed { id, result, step, .. } => {
+                self.handle_query_progress(id, result, step.last);
+            },
+            kad::Event::RoutingUpdated { peer, is_new_peer: true, .. } => {
+                info!("Node:Kad: Added {} to the routing table", peer);
+            },
+            other => debug!("Node:Kad: {other:?}"),
+        }
+    }
+
+
+    fn handle_query_progress(&mut self, query_id: QueryId, result: QueryResult, last: bool) {
+        match result {
+            QueryResult::Bootstrap(Ok(ok)) => {
+                if ok.num_remaining == 0 {
+                    info!("Node:Kad: Routing table refresh finished");
+                }
+            },
+            QueryResult::Bootstrap(Err(e)) => warn!("Node:Kad: Routing table refresh failed: {}", e),
+            QueryResult::StartProviding(result) => {
+                if let Some(PendingQuery::StartProviding(responder)) = self.pending_queries.remove(&query_id) {
+                    let _ = responder.send(result.map(|_| ()).map_err(|e| NodeError::Kademlia(e.to_string())));
+                }
+            },
+            QueryResult::GetProviders(result) => {
+                if let Some(PendingQuery::GetProviders { providers, .. }) = self.pending_queries.get_mut(&query_id) {
+                    if let Ok(GetProvidersOk::FoundProviders { providers: found, .. }) = &result {
+                        providers.extend(found.iter().copied());
+                    }
+                }
+                if !last && result.is_ok() {
+                    return;
+                }
+                // An empty lookup ends with a not-found error, which is an empty set to the caller
+                if let Some(PendingQuery::GetProviders { providers, responder }) = self.pending_queries.remove(&query_id) {
+                    let _ = responder.send(Ok(providers));
+                }
+            },
+            QueryResult::RepublishProvider(Err(e)) => warn!("Node:Kad: Provider republish failed: {}", e),
+            other => debug!("Node:Kad: Query {:?} progressed: {:?}", query_id, other),
+        }
+    }
+}
+
+
+/// Returns the peer id named by the trailing `/p2p/<peer-id>` component, if any.
+pub(super) fn peer_id_of(address: &Multiaddr) -> Option<PeerId> {
+    match address.iter().last() {
+        Some(Protocol::P2p(peer_id)) => Some(peer_id),
+        _ => None,
+    }
+}
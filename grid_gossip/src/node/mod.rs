@@ -0,0 +1,336 @@
+mod handle;
+mod kademlia;
+mod scoring;
+
+pub use handle::{ NodeError, NodeHandle };
+
+use async_io::Timer;
+use futures::{ channel::mpsc, future::Either, select, StreamExt };
+use libp2p::{
+    core::{ muxing::StreamMuxerBox, transport::{ Boxed, OrTransport }, upgrade },
+    gossipsub::{ self, IdentTopic, MessageAcceptance, MessageId },
+    identify,
+    identity,
+    kad::QueryId,
+    mdns,
+    noise,
+    ping,
+    swarm::{ self, Swarm, SwarmEvent },
+    tcp,
+    yamux,
+    Multiaddr, PeerId, Transport,
+};
+use libp2p_quic as quic;
+use log::{ debug, info, warn };
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::behaviour::{ GridBehaviour, GridBehaviourEvent };
+use crate::message::{ Dispatcher, Envelope, MessageHandler, MessageKind };
+use crate::peers::PeerTable;
+use crate::scoring::{ BanList, ScoringConfig };
+use crate::validation::{ MessageValidator, Validation, ValidationPipeline };
+use handle::Command;
+use kademlia::PendingQuery;
+
+/// Number of commands a `NodeHandle` can queue before senders wait.
+const COMMAND_BUFFER: usize = 64;
+
+/// Runtime settings for a single grid node, usually built from the command line.
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    pub tcp_listen_addrs: Vec<Multiaddr>,
+    pub quic_listen_addrs: Vec<Multiaddr>,
+    /// Publicly reachable addresses announced to peers, e.g. in DHT provider records.
+    pub external_addrs: Vec<Multiaddr>,
+    pub bootstrap_addrs: Vec<Multiaddr>,
+    pub topics: Vec<String>,
+    pub enable_mdns: bool,
+    /// How often the Kademlia routing table is refreshed with a bootstrap query.
+    pub kad_refresh_interval: Duration,
+    pub scoring: ScoringConfig,
+    /// File the ban list is persisted to. Without one, bans last until the node stops.
+    pub ban_list_path: Option<PathBuf>,
+}
+
+pub struct Node {
+    swarm: Swarm<GridBehaviour>,
+    local_node_key: identity::Keypair,
+    config: NodeConfig,
+    topics: Vec<IdentTopic>,
+    dispatcher: Dispatcher,
+    validation: ValidationPipeline,
+    pending_queries: HashMap<QueryId, PendingQuery>,
+    peer_table: PeerTable,
+    ban_list: BanList,
+    command_sender: mpsc::Sender<Command>,
+    command_receiver: mpsc::Receiver<Command>,
+}
+
+impl Node {
+    pub fn new(local_node_key: identity::Keypair, config: NodeConfig) -> Result<Self, Box<dyn Error>> {
+        let local_node_id = PeerId::from(local_node_key.public());
+        info!("Node:Init: Id: {}", local_node_id);
+
+        let transport = build_transport(&local_node_key)?;
+
+        // Node: Compose gossipsub and mDNS, then subscribe to every configured topic
+        let mut behaviour = GridBehaviour::new(&local_node_key, config.enable_mdns)?;
+        let mut topics = Vec::new();
+        for topic_name in &config.topics {
+            let topic = IdentTopic::new(topic_name.as_str());
+            behaviour.gossipsub.subscribe(&topic)?;
+            info!("Node:Init: Subscribed to topic: {}", topic);
+            topics.push(topic);
+        }
+
+        // Node: Score peers on every topic so misbehaving peers lose mesh slots and get banned
+        config.scoring.validate()?;
+        behaviour.gossipsub.with_peer_score(config.scoring.peer_score_params(&topics), config.scoring.thresholds())?;
+        let ban_list = BanList::load(config.ban_list_path.clone())?;
+
+        // Node: Create Node Swarm. A Swarm controls the state of the network and how it behaves.
+        let swarm = Swarm::new(
+            transport,
+            behaviour,
+            local_node_id,
+            swarm::Config::with_async_std_executor(),
+        );
+
+        let (command_sender, command_receiver) = mpsc::channel(COMMAND_BUFFER);
+
+        let mut node = Node {
+            swarm,
+            local_node_key,
+            config,
+            topics,
+            dispatcher: Dispatcher::new(),
+            validation: ValidationPipeline::default(),
+            pending_queries: HashMap::new(),
+            peer_table: PeerTable::new(),
+            ban_list,
+            command_sender,
+            command_receiver,
+        };
+        node.apply_ban_list();
+        Ok(node)
+    }
+
+
+    pub fn local_peer_id(&self) -> &PeerId {
+        self.swarm.local_peer_id()
+    }
+
+
+    /// Returns a handle for publishing once the node is running.
+    pub fn handle(&self) -> NodeHandle {
+        NodeHandle::new(self.command_sender.clone(), self.local_node_key.clone())
+    }
+
+
+    /// Registers a handler for incoming messages of `kind`. Handlers run on the
+    /// node's event loop and should hand heavy work off to their own tasks.
+    pub fn register_handler(&mut self, kind: MessageKind, handler: impl MessageHandler + 'static) {
+        self.dispatcher.register(kind, handler);
+    }
+
+
+    /// Adds an application validator for incoming messages of `kind`. It runs
+    /// after the built-in envelope checks and the default validators.
+    pub fn register_validator(&mut self, kind: MessageKind, validator: impl MessageValidator + 'static) {
+        self.validation.register(kind, validator);
+    }
+
+
+    pub async fn run(mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // Node: Initialize Swarm listeners for each MultiAddr
+        for address in &self.config.tcp_listen_addrs {
+            let listener_id = self.swarm.listen_on(address.clone())?;
+            info!("Node:Init: TCP listener {:?} requested on {}", listener_id, address);
+        }
+        for address in &self.config.quic_listen_addrs {
+            let listener_id = self.swarm.listen_on(address.clone())?;
+            info!("Node:Init: Quic listener {:?} requested on {}", listener_id, address);
+        }
+
+        for address in &self.config.external_addrs {
+            self.swarm.add_external_address(address.clone());
+            info!("Node:Init: External address set: {}", address);
+        }
+
+        // Node: Dial bootstrap peers, a failed dial is not fatal
+        for address in &self.config.bootstrap_addrs {
+            match self.swarm.dial(address.clone()) {
+                Ok(()) => info!("Node:Init: Dialing bootstrap peer: {}", address),
+                Err(e) => warn!("Node:Init: Failed to dial bootstrap peer {}: {}", address, e),
+            }
+        }
+        self.add_bootstrap_peers();
+        self.bootstrap_kademlia();
+
+        let mut kad_refresh = Timer::interval(self.config.kad_refresh_interval).fuse();
+        let mut score_check = Timer::interval(self.config.scoring.check_interval).fuse();
+
+        loop {
+            select! {
+                event = self.swarm.select_next_some() => self.handle_swarm_event(event),
+                command = self.command_receiver.select_next_some() => self.handle_command(command),
+                _ = kad_refresh.select_next_some() => self.bootstrap_kademlia(),
+                _ = score_check.select_next_some() => self.check_peer_scores(),
+            }
+        }
+    }
+
+
+    fn handle_swarm_event<E: std::fmt::Debug>(&mut self, event: SwarmEvent<GridBehaviourEvent, E>) {
+        match event {
+            SwarmEvent::NewListenAddr { address, .. } => info!("Node:Event: Listening on: {address}"),
+            SwarmEvent::Behaviour(event) => self.handle_behaviour_event(event),
+            SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
+                info!("Node:Event: Connection established: {} ({})", peer_id, endpoint.get_remote_address());
+                self.peer_table.connection_established(peer_id);
+            },
+            SwarmEvent::ConnectionClosed { peer_id, cause, .. } => {
+                info!("Node:Event: Connection closed: {} ({:?})", peer_id, cause);
+                self.peer_table.connection_closed(peer_id);
+            },
+            SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+                warn!("Node:Event: Outgoing connection to {:?} failed: {}", peer_id, error);
+            },
+            other => debug!("Node:Event: {other:?}"),
+        }
+    }
+
+
+    fn handle_behaviour_event(&mut self, event: GridBehaviourEvent) {
+        match event {
+            GridBehaviourEvent::Gossipsub(gossipsub::Event::Message { propagation_source, message_id, message }) => {
+                let source = message.source.unwrap_or(propagation_source);
+                let verdict = self.validation.validate(&source, &message.data);
+
+                // Gossipsub holds the message until it hears back, forwarding only accepted ones
+                let acceptance = match &verdict {
+                    Ok(_) => MessageAcceptance::Accept,
+                    Err(validation) => validation.acceptance(),
+                };
+                if let Err(e) = self.swarm.behaviour_mut().gossipsub
+                    .report_message_validation_result(&message_id, &propagation_source, acceptance) {
+                    warn!("Node:Gossipsub: Failed to report validation of {}: {}", message_id, e);
+                }
+
+                match verdict {
+                    Ok(envelope) => {
+                        debug!("Node:Gossipsub: {:?} message {} from {}", envelope.kind, message_id, source);
+                        self.dispatcher.dispatch(&source, &envelope);
+                    },
+                    Err(validation) => {
+                        warn!("Node:Gossipsub: Dropped message {} from {} via {}: {:?}",
+                            message_id, source, propagation_source, validation);
+                        if matches!(validation, Validation::Reject(_)) {
+                            self.check_peer_score(propagation_source);
+                        }
+                    },
+                }
+            },
+            GridBehaviourEvent::Gossipsub(event) => debug!("Node:Gossipsub: {event:?}"),
+            GridBehaviourEvent::Kad(event) => self.handle_kad_event(event),
+            GridBehaviourEvent::Identify(identify::Event::Received { peer_id, info }) => {
+                debug!("Node:Identify: {} runs {} with {} listen addrs", peer_id, info.agent_version, info.listen_addrs.len());
+                // Identified listen addresses make peers that dialed in routable in the DHT
+                for address in &info.listen_addrs {
+                    self.swarm.behaviour_mut().kad.add_address(&peer_id, address.clone());
+                }
+                self.peer_table.identified(peer_id, &info);
+            },
+            GridBehaviourEvent::Identify(identify::Event::Error { peer_id, error }) => {
+                debug!("Node:Identify: Failed to identify {}: {}", peer_id, error);
+            },
+            GridBehaviourEvent::Identify(event) => debug!("Node:Identify: {event:?}"),
+            GridBehaviourEvent::Ping(ping::Event { peer, result: Ok(rtt), .. }) => {
+                self.peer_table.pinged(peer, rtt);
+            },
+            GridBehaviourEvent::Ping(ping::Event { peer, result: Err(e), .. }) => {
+                debug!("Node:Ping: Ping to {} failed: {}", peer, e);
+            },
+            GridBehaviourEvent::Mdns(mdns::Event::Discovered(peers)) => {
+                for (peer_id, address) in peers {
+                    info!("Node:mDNS: Discovered peer {} at {}", peer_id, address);
+                    self.swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                    self.swarm.behaviour_mut().kad.add_address(&peer_id, address);
+                }
+            },
+            GridBehaviourEvent::Mdns(mdns::Event::Expired(peers)) => {
+                for (peer_id, address) in peers {
+                    info!("Node:mDNS: Peer {} at {} expired", peer_id, address);
+                    // A peer can be announced on several addresses, keep it until the last one expires
+                    let still_discovered = self.swarm.behaviour().mdns.as_ref()
+                        .is_some_and(|mdns| mdns.discovered_nodes().any(|node| node == &peer_id));
+                    if !still_discovered {
+                        self.swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
+                    }
+                }
+            },
+        }
+    }
+
+
+    fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Publish { topic, envelope, responder } => {
+                let _ = responder.send(self.publish(topic, &envelope));
+            },
+            Command::StartProviding { key, responder } => self.start_providing(key, responder),
+            Command::StopProviding { key } => self.swarm.behaviour_mut().kad.stop_providing(&key),
+            Command::GetProviders { key, responder } => self.get_providers(key, responder),
+            Command::Peers { responder } => {
+                let _ = responder.send(Ok(self.peer_table.snapshot()));
+            },
+            Command::Ban { peer_id, responder } => {
+                let _ = responder.send(self.ban_peer(peer_id));
+            },
+            Command::Unban { peer_id, responder } => {
+                let _ = responder.send(self.unban_peer(peer_id));
+            },
+            Command::BannedPeers { responder } => {
+                let _ = responder.send(Ok(self.ban_list.peers()));
+            },
+        }
+    }
+
+
+    fn publish(&mut self, topic: Option<String>, envelope: &Envelope) -> Result<MessageId, NodeError> {
+        let topic = match topic {
+            Some(name) => self.topics.iter().find(|topic| topic.to_string() == name)
+                .ok_or(NodeError::UnknownTopic(name))?,
+            None => self.topics.first()
+                .ok_or_else(|| NodeError::UnknownTopic("<default>".to_string()))?,
+        }.clone();
+
+        let data = envelope.encode().map_err(NodeError::Message)?;
+        self.swarm.behaviour_mut().gossipsub.publish(topic, data).map_err(NodeError::Publish)
+    }
+}
+
+
+fn build_transport(
+    local_node_key: &identity::Keypair
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>, Box<dyn Error>> {
+    let tcp_transport = tcp::async_io::Transport::new(tcp::Config::default().nodelay(true))
+        .upgrade(upgrade::Version::V1Lazy)
+        .authenticate(noise::Config::new(local_node_key)?)
+        .multiplex(yamux::Config::default())
+        .timeout(Duration::from_secs(20))
+        .boxed();
+    let quic_transport = quic::async_std::Transport::new(quic::Config::new(local_node_key));
+
+    let transport = OrTransport::new(quic_transport, tcp_transport)
+        .map(|either_output, _| match either_output {
+            Either::Left((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+            Either::Right((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+        })
+        .boxed();
+
+    Ok(transport)
+}
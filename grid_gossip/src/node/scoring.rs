@@ -0,0 +1,74 @@
+use libp2p::PeerId;
+use log::{ info, warn };
+
+use super::{ Node, NodeError };
+
+impl Node {
+    /// Blocks every peer on the ban list loaded at startup.
+    pub(super) fn apply_ban_list(&mut self) {
+        for peer_id in self.ban_list.peers() {
+            self.block_peer(peer_id);
+        }
+    }
+
+
+    /// Records current gossipsub scores and bans connected peers that fell below the cutoff.
+    pub(super) fn check_peer_scores(&mut self) {
+        let connected: Vec<PeerId> = self.swarm.connected_peers().copied().collect();
+        for peer_id in connected {
+            self.check_peer_score(peer_id);
+        }
+    }
+
+
+    /// Also called right after a rejected message, since penalties decay before the next periodic check.
+    pub(super) fn check_peer_score(&mut self, peer_id: PeerId) {
+        let score = match self.swarm.behaviour().gossipsub.peer_score(&peer_id) {
+            Some(score) => score,
+            None => return,
+        };
+        self.peer_table.scored(&peer_id, score);
+
+        if score < self.config.scoring.ban_threshold {
+            warn!("Node:Scoring: Peer {} scored {:.1}, below ban threshold {:.1}",
+                peer_id, score, self.config.scoring.ban_threshold);
+            if let Err(e) = self.ban_peer(peer_id) {
+                warn!("Node:Scoring: {}", e);
+            }
+        }
+    }
+
+
+    pub(super) fn ban_peer(&mut self, peer_id: PeerId) -> Result<bool, NodeError> {
+        // Block first so the peer is cut off even if the ban list cannot be written
+        self.block_peer(peer_id);
+        let newly_banned = self.ban_list.ban(peer_id).map_err(NodeError::BanList)?;
+        if newly_banned {
+            info!("Node:Scoring: Banned peer {}", peer_id);
+        }
+        Ok(newly_banned)
+    }
+
+
+    pub(super) fn unban_peer(&mut self, peer_id: PeerId) -> Result<bool, NodeError> {
+        let behaviour = self.swarm.behaviour_mut();
+        behaviour.blocked_peers.unblock_peer(peer_id);
+        behaviour.gossipsub.remove_blacklisted_peer(&peer_id);
+
+        let was_banned = self.ban_list.unban(&peer_id).map_err(NodeError::BanList)?;
+        if was_banned {
+            info!("Node:Scoring: Unbanned peer {}", peer_id);
+        }
+        Ok(was_banned)
+    }
+
+
+    fn block_peer(&mut self, peer_id: PeerId) {
+        let behaviour = self.swarm.behaviour_mut();
+        // Blocking closes open connections and denies new ones in both directions
+        behaviour.blocked_peers.block_peer(peer_id);
+        behaviour.gossipsub.blacklist_peer(&peer_id);
+        behaviour.gossipsub.remove_explicit_peer(&peer_id);
+        behaviour.kad.remove_peer(&peer_id);
+    }
+}
@@ -0,0 +1,158 @@
+use libp2p::{ identify, Multiaddr, PeerId };
+use std::collections::HashMap;
+use std::time::{ Duration, SystemTime };
+
+/// What this node knows about one remote peer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerInfo {
+    pub peer_id: PeerId,
+    /// Listen addresses the peer reported through identify.
+    pub listen_addrs: Vec<Multiaddr>,
+    pub agent_version: Option<String>,
+    pub protocols: Vec<String>,
+    /// Round-trip time of the latest successful ping.
+    pub rtt: Option<Duration>,
+    /// Gossipsub score at the latest score check.
+    pub score: Option<f64>,
+    /// Number of currently open connections, zero once the peer disconnected.
+    pub connections: usize,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+}
+
+impl PeerInfo {
+    fn new(peer_id: PeerId, now: SystemTime) -> Self {
+        PeerInfo {
+            peer_id,
+            listen_addrs: Vec::new(),
+            agent_version: None,
+            protocols: Vec::new(),
+            rtt: None,
+            score: None,
+            connections: 0,
+            first_seen: now,
+            last_seen: now,
+        }
+    }
+
+
+    pub fn is_connected(&self) -> bool {
+        self.connections > 0
+    }
+}
+
+/// In-memory record of every peer seen since the node started. Disconnected
+/// peers are kept with a connection count of zero.
+#[derive(Debug, Default)]
+pub struct PeerTable {
+    peers: HashMap<PeerId, PeerInfo>,
+}
+
+impl PeerTable {
+    pub fn new() -> Self {
+        PeerTable::default()
+    }
+
+
+    pub fn connection_established(&mut self, peer_id: PeerId) {
+        self.touch(peer_id).connections += 1;
+    }
+
+
+    pub fn connection_closed(&mut self, peer_id: PeerId) {
+        let peer = self.touch(peer_id);
+        peer.connections = peer.connections.saturating_sub(1);
+    }
+
+
+    pub fn identified(&mut self, peer_id: PeerId, info: &identify::Info) {
+        let peer = self.touch(peer_id);
+        peer.listen_addrs = info.listen_addrs.clone();
+        peer.agent_version = Some(info.agent_version.clone());
+        peer.protocols = info.protocols.iter().map(|protocol| protocol.to_string()).collect();
+    }
+
+
+    pub fn pinged(&mut self, peer_id: PeerId, rtt: Duration) {
+        self.touch(peer_id).rtt = Some(rtt);
+    }
+
+
+    /// Records a score without touching `last_seen`, scores are polled rather than observed.
+    pub fn scored(&mut self, peer_id: &PeerId, score: f64) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.score = Some(score);
+        }
+    }
+
+
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerInfo> {
+        self.peers.get(peer_id)
+    }
+
+
+    /// Returns a copy of every entry, connected peers first.
+    pub fn snapshot(&self) -> Vec<PeerInfo> {
+        let mut peers: Vec<PeerInfo> = self.peers.values().cloned().collect();
+        peers.sort_by(|a, b| b.is_connected().cmp(&a.is_connected()).then(b.last_seen.cmp(&a.last_seen)));
+        peers
+    }
+
+
+    pub fn connected_count(&self) -> usize {
+        self.peers.values().filter(|peer| peer.is_connected()).count()
+    }
+
+
+    fn touch(&mut self, peer_id: PeerId) -> &mut PeerInfo {
+        let now = SystemTime::now();
+        let peer = self.peers.entry(peer_id).or_insert_with(|| PeerInfo::new(peer_id, now));
+        peer.last_seen = now;
+        peer
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::peers::PeerTable;
+    use libp2p::PeerId;
+    use std::time::Duration;
+
+    #[test]
+    fn test_connection_counting() {
+        let mut table = PeerTable::new();
+        let peer_id = PeerId::random();
+
+        table.connection_established(peer_id);
+        table.connection_established(peer_id);
+        table.connection_closed(peer_id);
+        let still_connected = table.get(&peer_id).unwrap().is_connected();
+        table.connection_closed(peer_id);
+        table.connection_closed(peer_id);
+
+        let peer = table.get(&peer_id).unwrap();
+        assert!(still_connected);
+        assert_eq!(peer.connections, 0);
+        assert_eq!(table.connected_count(), 0);
+        assert!(peer.first_seen <= peer.last_seen);
+    }
+
+    #[test]
+    fn test_snapshot_lists_connected_first() {
+        let mut table = PeerTable::new();
+        let gone = PeerId::random();
+        let live = PeerId::random();
+
+        table.connection_established(gone);
+        table.connection_closed(gone);
+        table.connection_established(live);
+        table.pinged(live, Duration::from_millis(3));
+
+        let snapshot = table.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].peer_id, live);
+        assert_eq!(snapshot[0].rtt, Some(Duration::from_millis(3)));
+        assert_eq!(snapshot[1].peer_id, gone);
+    }
+}
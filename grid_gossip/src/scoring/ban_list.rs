@@ -0,0 +1,136 @@
+use libp2p::PeerId;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Set of banned peers, written to a file on every change so bans survive
+/// restarts. The file holds one peer id per line; `#` starts a comment.
+#[derive(Debug, Default)]
+pub struct BanList {
+    path: Option<PathBuf>,
+    banned: HashSet<PeerId>,
+}
+
+impl BanList {
+    /// Loads the ban list at `path`, or starts an empty one if the file does not
+    /// exist yet. Without a path the list only lives in memory.
+    pub fn load(path: Option<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let mut banned = HashSet::new();
+
+        if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("BanList: Failed to read {}: {}", path.display(), e))?;
+            for (index, line) in contents.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or("").trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let peer_id: PeerId = line.parse()
+                    .map_err(|e| format!("BanList: {}:{}: invalid peer id: {}", path.display(), index + 1, e))?;
+                banned.insert(peer_id);
+            }
+        }
+
+        Ok(BanList { path, banned })
+    }
+
+
+    /// Returns false if the peer was already banned.
+    pub fn ban(&mut self, peer_id: PeerId) -> io::Result<bool> {
+        if !self.banned.insert(peer_id) {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+
+    /// Returns false if the peer was not banned.
+    pub fn unban(&mut self, peer_id: &PeerId) -> io::Result<bool> {
+        if !self.banned.remove(peer_id) {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+
+    pub fn contains(&self, peer_id: &PeerId) -> bool {
+        self.banned.contains(peer_id)
+    }
+
+
+    pub fn peers(&self) -> Vec<PeerId> {
+        let mut peers: Vec<PeerId> = self.banned.iter().copied().collect();
+        peers.sort();
+        peers
+    }
+
+
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut contents = String::from("# Peers banned by grid_node, one peer id per line\n");
+        for peer_id in self.peers() {
+            contents.push_str(&peer_id.to_string());
+            contents.push('\n');
+        }
+
+        // Write then rename so a crash never leaves a half-written list behind
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::scoring::BanList;
+    use libp2p::PeerId;
+    use std::path::PathBuf;
+
+    fn ban_list_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("grid_gossip_{}_{}.bans", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_bans_survive_reload() {
+        let path = ban_list_path("reload");
+        let banned = PeerId::random();
+        let forgiven = PeerId::random();
+
+        let mut ban_list = BanList::load(Some(path.clone())).unwrap();
+        let first_ban = ban_list.ban(banned).unwrap();
+        let second_ban = ban_list.ban(banned).unwrap();
+        ban_list.ban(forgiven).unwrap();
+        ban_list.unban(&forgiven).unwrap();
+
+        let reloaded = BanList::load(Some(path.clone())).unwrap();
+
+        std::fs::remove_file(&path)
+            .expect("Failed to remove ban list.");
+
+        assert!(first_ban);
+        assert!(!second_ban);
+        assert_eq!(reloaded.peers(), vec![banned]);
+    }
+
+    #[test]
+    fn test_invalid_entries_are_reported() {
+        let path = ban_list_path("invalid");
+        std::fs::write(&path, "# comment\n\nnot-a-peer-id\n").unwrap();
+
+        let result = BanList::load(Some(path.clone()));
+
+        std::fs::remove_file(&path)
+            .expect("Failed to remove ban list.");
+
+        assert!(result.is_err());
+    }
+}
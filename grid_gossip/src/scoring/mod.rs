@@ -0,0 +1,121 @@
+mod ban_list;
+
+pub use ban_list::BanList;
+
+use libp2p::gossipsub::{ IdentTopic, PeerScoreParams, PeerScoreThresholds, TopicScoreParams };
+use std::time::Duration;
+
+/// Gossipsub peer scoring knobs plus the score below which a peer is banned.
+/// The same topic parameters apply to every topic the node subscribes to.
+#[derive(Debug, Clone)]
+pub struct ScoringConfig {
+    pub topic_weight: f64,
+    /// P1: reward per second spent in the mesh, capped at one hour.
+    pub time_in_mesh_weight: f64,
+    /// P2: reward per message first delivered by the peer.
+    pub first_delivery_weight: f64,
+    /// P4: penalty applied to the square of the rejected message count.
+    pub invalid_message_weight: f64,
+    pub gossip_threshold: f64,
+    pub publish_threshold: f64,
+    pub graylist_threshold: f64,
+    /// Peers scoring below this are disconnected, blocked and added to the ban list.
+    /// Graylisted peers stop accumulating penalties, so this should not sit far
+    /// below `graylist_threshold`.
+    pub ban_threshold: f64,
+    /// How often connected peers are checked against `ban_threshold`.
+    pub check_interval: Duration,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig {
+            topic_weight: 1.0,
+            time_in_mesh_weight: 0.01,
+            first_delivery_weight: 1.0,
+            invalid_message_weight: -10.0,
+            gossip_threshold: -10.0,
+            publish_threshold: -50.0,
+            graylist_threshold: -80.0,
+            ban_threshold: -80.0,
+            check_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ScoringConfig {
+    pub fn topic_params(&self) -> TopicScoreParams {
+        TopicScoreParams {
+            topic_weight: self.topic_weight,
+            time_in_mesh_weight: self.time_in_mesh_weight,
+            time_in_mesh_quantum: Duration::from_secs(1),
+            time_in_mesh_cap: 3600.0,
+            first_message_deliveries_weight: self.first_delivery_weight,
+            first_message_deliveries_decay: 0.5,
+            first_message_deliveries_cap: 50.0,
+            // Grid topics can be quiet for long stretches, so low mesh delivery rates are not penalized
+            mesh_message_deliveries_weight: 0.0,
+            mesh_failure_penalty_weight: 0.0,
+            invalid_message_deliveries_weight: self.invalid_message_weight,
+            invalid_message_deliveries_decay: 0.3,
+            ..TopicScoreParams::default()
+        }
+    }
+
+
+    pub fn peer_score_params(&self, topics: &[IdentTopic]) -> PeerScoreParams {
+        let mut params = PeerScoreParams::default();
+        for topic in topics {
+            params.topics.insert(topic.hash(), self.topic_params());
+        }
+        params
+    }
+
+
+    pub fn thresholds(&self) -> PeerScoreThresholds {
+        PeerScoreThresholds {
+            gossip_threshold: self.gossip_threshold,
+            publish_threshold: self.publish_threshold,
+            graylist_threshold: self.graylist_threshold,
+            ..PeerScoreThresholds::default()
+        }
+    }
+
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ban_threshold > self.publish_threshold {
+            return Err("Scoring: ban threshold must be <= publish threshold".to_string());
+        }
+        self.thresholds().validate().map_err(|e| format!("Scoring: {}", e))?;
+        self.topic_params().validate().map_err(|e| format!("Scoring: {}", e))?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::scoring::ScoringConfig;
+    use libp2p::gossipsub::IdentTopic;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let config = ScoringConfig::default();
+        let params = config.peer_score_params(&[IdentTopic::new("grid_topic")]);
+
+        assert!(config.validate().is_ok());
+        assert!(params.validate().is_ok());
+        assert_eq!(params.topics.len(), 1);
+    }
+
+    #[test]
+    fn test_inconsistent_thresholds_are_refused() {
+        let ban_above_publish = ScoringConfig { ban_threshold: -10.0, ..ScoringConfig::default() };
+        let positive_gossip = ScoringConfig { gossip_threshold: 1.0, ..ScoringConfig::default() };
+        let rewarding_invalid = ScoringConfig { invalid_message_weight: 1.0, ..ScoringConfig::default() };
+
+        assert!(ban_above_publish.validate().is_err());
+        assert!(positive_gossip.validate().is_err());
+        assert!(rewarding_invalid.validate().is_err());
+    }
+}
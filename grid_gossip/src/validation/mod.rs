@@ -0,0 +1,199 @@
+use libp2p::{ gossipsub::MessageAcceptance, PeerId };
+use log::debug;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::message::{ unix_time_millis, Envelope, MessageKind, SignedPayload };
+
+/// How far into the future an envelope timestamp may be before it is ignored.
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(300);
+
+/// How old an envelope may be before it is ignored. Bounds replays of messages
+/// that already fell out of the gossipsub duplicate cache.
+pub const DEFAULT_MAX_MESSAGE_AGE: Duration = Duration::from_secs(600);
+
+/// The verdict on one message, reported back to gossipsub.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Validation {
+    /// Deliver locally and forward.
+    Accept,
+    /// Drop and penalize the forwarding peer. Use for provably invalid content.
+    Reject(String),
+    /// Drop without a penalty, e.g. stale or not yet relevant content.
+    Ignore(String),
+}
+
+impl Validation {
+    pub fn acceptance(&self) -> MessageAcceptance {
+        match self {
+            Validation::Accept => MessageAcceptance::Accept,
+            Validation::Reject(_) => MessageAcceptance::Reject,
+            Validation::Ignore(_) => MessageAcceptance::Ignore,
+        }
+    }
+}
+
+/// Application-level check on a decoded envelope. Runs on the node's event
+/// loop, so it must be cheap and must not block.
+pub trait MessageValidator: Send {
+    fn validate(&self, source: &PeerId, envelope: &Envelope) -> Validation;
+}
+
+impl<F> MessageValidator for F
+where
+    F: Fn(&PeerId, &Envelope) -> Validation + Send,
+{
+    fn validate(&self, source: &PeerId, envelope: &Envelope) -> Validation {
+        self(source, envelope)
+    }
+}
+
+/// Requires the payload to be a `SignedPayload` whose signature verifies.
+pub struct SignatureValidator;
+
+impl MessageValidator for SignatureValidator {
+    fn validate(&self, _source: &PeerId, envelope: &Envelope) -> Validation {
+        match SignedPayload::decode(&envelope.payload) {
+            Ok(signed) if signed.verify() => Validation::Accept,
+            Ok(_) => Validation::Reject("payload signature does not verify".to_string()),
+            Err(e) => Validation::Reject(format!("payload is not a signed payload: {}", e)),
+        }
+    }
+}
+
+/// Decodes raw gossipsub data, applies the envelope checks every message gets,
+/// then runs the validators registered for the envelope's kind in order.
+pub struct ValidationPipeline {
+    validators: HashMap<MessageKind, Vec<Box<dyn MessageValidator>>>,
+    max_clock_skew: Duration,
+    max_message_age: Duration,
+}
+
+impl Default for ValidationPipeline {
+    /// Transactions must carry a valid author signature.
+    fn default() -> Self {
+        let mut pipeline = ValidationPipeline::new(DEFAULT_MAX_CLOCK_SKEW, DEFAULT_MAX_MESSAGE_AGE);
+        pipeline.register(MessageKind::Transaction, SignatureValidator);
+        pipeline
+    }
+}
+
+impl ValidationPipeline {
+    /// Creates a pipeline with only the envelope checks and no kind validators.
+    pub fn new(max_clock_skew: Duration, max_message_age: Duration) -> Self {
+        ValidationPipeline {
+            validators: HashMap::new(),
+            max_clock_skew,
+            max_message_age,
+        }
+    }
+
+
+    pub fn register(&mut self, kind: MessageKind, validator: impl MessageValidator + 'static) {
+        self.validators.entry(kind).or_default().push(Box::new(validator));
+    }
+
+
+    /// Returns the envelope if every check accepted it, otherwise the first
+    /// `Reject` or `Ignore` verdict.
+    pub fn validate(&self, source: &PeerId, data: &[u8]) -> Result<Envelope, Validation> {
+        let envelope = Envelope::decode(data)
+            .map_err(|e| Validation::Reject(e.to_string()))?;
+
+        self.check_timestamp(&envelope)?;
+
+        for validator in self.validators.get(&envelope.kind).into_iter().flatten() {
+            match validator.validate(source, &envelope) {
+                Validation::Accept => continue,
+                verdict => {
+                    debug!("Validation: {:?} message from {} failed: {:?}", envelope.kind, source, verdict);
+                    return Err(verdict);
+                }
+            }
+        }
+        Ok(envelope)
+    }
+
+
+    fn check_timestamp(&self, envelope: &Envelope) -> Result<(), Validation> {
+        let now = unix_time_millis();
+        if envelope.timestamp > now.saturating_add(self.max_clock_skew.as_millis() as u64) {
+            return Err(Validation::Ignore("timestamp is in the future".to_string()));
+        }
+        if envelope.timestamp < now.saturating_sub(self.max_message_age.as_millis() as u64) {
+            return Err(Validation::Ignore("message is too old".to_string()));
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::message::{ Envelope, MessageKind, SignedPayload };
+    use crate::validation::{ Validation, ValidationPipeline };
+    use libp2p::{ identity::Keypair, PeerId };
+
+    fn encoded(kind: MessageKind, payload: Vec<u8>) -> Vec<u8> {
+        Envelope::new(kind, payload).encode().unwrap()
+    }
+
+    #[test]
+    fn test_malformed_envelope_is_rejected() {
+        let pipeline = ValidationPipeline::default();
+
+        let result = pipeline.validate(&PeerId::random(), b"Hello, Meow!");
+
+        assert!(matches!(result, Err(Validation::Reject(_))));
+    }
+
+    #[test]
+    fn test_transactions_require_valid_signature() {
+        let pipeline = ValidationPipeline::default();
+        let source = PeerId::random();
+        let keypair = Keypair::generate_ed25519();
+
+        let signed = SignedPayload::sign(&keypair, b"transfer 369".to_vec()).unwrap();
+        let mut forged = signed.clone();
+        forged.body = b"transfer 963".to_vec();
+
+        let valid = pipeline.validate(&source, &encoded(MessageKind::Transaction, signed.encode().unwrap()));
+        let invalid = pipeline.validate(&source, &encoded(MessageKind::Transaction, forged.encode().unwrap()));
+        let unsigned = pipeline.validate(&source, &encoded(MessageKind::Transaction, b"transfer 369".to_vec()));
+        let status = pipeline.validate(&source, &encoded(MessageKind::Status, b"up".to_vec()));
+
+        assert!(valid.is_ok());
+        assert!(matches!(invalid, Err(Validation::Reject(_))));
+        assert!(matches!(unsigned, Err(Validation::Reject(_))));
+        assert!(status.is_ok());
+    }
+
+    #[test]
+    fn test_stale_messages_are_ignored() {
+        let pipeline = ValidationPipeline::default();
+        let mut envelope = Envelope::new(MessageKind::Status, Vec::new());
+        envelope.timestamp = 1;
+
+        let result = pipeline.validate(&PeerId::random(), &envelope.encode().unwrap());
+
+        assert!(matches!(result, Err(Validation::Ignore(_))));
+    }
+
+    #[test]
+    fn test_custom_validator_runs_for_its_kind() {
+        let mut pipeline = ValidationPipeline::default();
+        pipeline.register(MessageKind::Job, |_: &PeerId, envelope: &Envelope| {
+            match envelope.payload.is_empty() {
+                true => Validation::Reject("empty job".to_string()),
+                false => Validation::Accept,
+            }
+        });
+        let source = PeerId::random();
+
+        let empty = pipeline.validate(&source, &encoded(MessageKind::Job, Vec::new()));
+        let job = pipeline.validate(&source, &encoded(MessageKind::Job, b"job".to_vec()));
+
+        assert!(matches!(empty, Err(Validation::Reject(_))));
+        assert!(job.is_ok());
+    }
+}
@@ -0,0 +1,244 @@
+use async_std::future::timeout;
+use futures::{ channel::mpsc, StreamExt };
+use grid_gossip::message::{ Envelope, MessageKind };
+use grid_gossip::node::{ Node, NodeConfig, NodeHandle };
+use grid_gossip::scoring::{ BanList, ScoringConfig };
+use libp2p::{ identity, Multiaddr, PeerId };
+use std::time::Duration;
+
+fn free_tcp_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn local_config(tcp_port: u16, bootstrap_addrs: Vec<Multiaddr>) -> NodeConfig {
+    let tcp_address: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", tcp_port).parse().unwrap();
+    NodeConfig {
+        tcp_listen_addrs: vec![tcp_address.clone()],
+        quic_listen_addrs: Vec::new(),
+        external_addrs: vec![tcp_address],
+        bootstrap_addrs,
+        topics: vec!["grid_topic".to_string()],
+        enable_mdns: false,
+        kad_refresh_interval: Duration::from_secs(300),
+        scoring: ScoringConfig::default(),
+        ban_list_path: None,
+    }
+}
+
+/// Starts a node listening on a fresh local port and returns its handle and dialable address.
+fn spawn_node(bootstrap_addrs: Vec<Multiaddr>) -> (NodeHandle, Multiaddr) {
+    let port = free_tcp_port();
+    let key = identity::Keypair::generate_ed25519();
+    let address: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}/p2p/{}", port, PeerId::from(key.public()))
+        .parse()
+        .unwrap();
+
+    let node = Node::new(key, local_config(port, bootstrap_addrs)).unwrap();
+    let handle = node.handle();
+    async_std::task::spawn(node.run());
+    (handle, address)
+}
+
+#[async_std::test]
+async fn test_publish_reaches_subscribed_peer() {
+    let port = free_tcp_port();
+    let publisher_key = identity::Keypair::generate_ed25519();
+    let publisher_id = PeerId::from(publisher_key.public());
+
+    let publisher = Node::new(publisher_key, local_config(port, Vec::new())).unwrap();
+    let publisher_handle = publisher.handle();
+    async_std::task::spawn(publisher.run());
+
+    let bootstrap: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap();
+    let mut subscriber = Node::new(
+        identity::Keypair::generate_ed25519(),
+        local_config(free_tcp_port(), vec![bootstrap]),
+    ).unwrap();
+
+    let (sender, mut received) = mpsc::unbounded();
+    subscriber.register_handler(MessageKind::Job, move |source: &PeerId, envelope: &Envelope| {
+        let _ = sender.unbounded_send((*source, envelope.clone()));
+    });
+    async_std::task::spawn(subscriber.run());
+
+    // Publishing fails with InsufficientPeers until the subscription has propagated
+    let mut published = false;
+    for _ in 0..50 {
+        if publisher_handle.publish(MessageKind::Job, b"job-369".to_vec()).await.is_ok() {
+            published = true;
+            break;
+        }
+        async_std::task::sleep(Duration::from_millis(100)).await;
+    }
+
+    let (source, envelope) = timeout(Duration::from_secs(5), received.next())
+        .await
+        .expect("Message was not delivered")
+        .unwrap();
+
+    assert!(published);
+    assert_eq!(source, publisher_id);
+    assert_eq!(envelope.kind, MessageKind::Job);
+    assert_eq!(envelope.payload, b"job-369".to_vec());
+}
+
+
+#[async_std::test]
+async fn test_provider_record_lookup_through_bootstrap_peer() {
+    let (_bootstrap_handle, bootstrap_addr) = spawn_node(Vec::new());
+    let (provider_handle, provider_addr) = spawn_node(vec![bootstrap_addr.clone()]);
+    let (seeker_handle, _) = spawn_node(vec![bootstrap_addr]);
+
+    let key = b"job-artifact-369";
+    let mut provided = false;
+    for _ in 0..50 {
+        if provider_handle.start_providing(key).await.is_ok() {
+            provided = true;
+            break;
+        }
+        async_std::task::sleep(Duration::from_millis(100)).await;
+    }
+
+    let provider_id = match provider_addr.iter().last() {
+        Some(libp2p::multiaddr::Protocol::P2p(peer_id)) => peer_id,
+        _ => unreachable!(),
+    };
+
+    // ADD_PROVIDER is fire-and-forget, so the record can reach the bootstrap peer after the lookup
+    let mut found = false;
+    for _ in 0..50 {
+        let providers = timeout(Duration::from_secs(10), seeker_handle.get_providers(key))
+            .await
+            .expect("Provider lookup timed out")
+            .unwrap();
+        if providers.contains(&provider_id) {
+            found = true;
+            break;
+        }
+        async_std::task::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert!(provided);
+    assert!(found);
+}
+
+
+#[async_std::test]
+async fn test_peer_table_tracks_identified_peer() {
+    let (bootstrap_handle, bootstrap_addr) = spawn_node(Vec::new());
+    let (dialer_handle, _) = spawn_node(vec![bootstrap_addr.clone()]);
+
+    let bootstrap_id = match bootstrap_addr.iter().last() {
+        Some(libp2p::multiaddr::Protocol::P2p(peer_id)) => peer_id,
+        _ => unreachable!(),
+    };
+
+    // Identify and the first ping both run right after the connection is up
+    let mut identified = None;
+    for _ in 0..50 {
+        if let Some(peer) = dialer_handle.peer(&bootstrap_id).await.unwrap() {
+            if peer.agent_version.is_some() && peer.rtt.is_some() {
+                identified = Some(peer);
+                break;
+            }
+        }
+        async_std::task::sleep(Duration::from_millis(100)).await;
+    }
+    let peer = identified.expect("Peer was not identified and pinged");
+    let bootstrap_peers = bootstrap_handle.peers().await.unwrap();
+
+    assert!(peer.is_connected());
+    assert!(peer.agent_version.unwrap().starts_with("grid_node/"));
+    assert!(peer.protocols.iter().any(|protocol| protocol == "/zgrid/kad/1.0.0"));
+    assert!(!peer.listen_addrs.is_empty());
+    assert_eq!(bootstrap_peers.iter().filter(|peer| peer.is_connected()).count(), 1);
+}
+
+
+#[async_std::test]
+async fn test_unsigned_transactions_are_not_delivered() {
+    let port = free_tcp_port();
+    let publisher = Node::new(identity::Keypair::generate_ed25519(), local_config(port, Vec::new())).unwrap();
+    let publisher_handle = publisher.handle();
+    async_std::task::spawn(publisher.run());
+
+    let bootstrap: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap();
+    let mut subscriber = Node::new(
+        identity::Keypair::generate_ed25519(),
+        local_config(free_tcp_port(), vec![bootstrap]),
+    ).unwrap();
+
+    let (sender, mut received) = mpsc::unbounded();
+    subscriber.register_handler(MessageKind::Transaction, move |_: &PeerId, envelope: &Envelope| {
+        let _ = sender.unbounded_send(envelope.clone());
+    });
+    async_std::task::spawn(subscriber.run());
+
+    let mut published_forged = false;
+    for _ in 0..50 {
+        if publisher_handle.publish(MessageKind::Transaction, b"unsigned tx".to_vec()).await.is_ok() {
+            published_forged = true;
+            break;
+        }
+        async_std::task::sleep(Duration::from_millis(100)).await;
+    }
+    publisher_handle.publish_signed(MessageKind::Transaction, b"signed tx".to_vec()).await.unwrap();
+
+    let envelope = timeout(Duration::from_secs(5), received.next())
+        .await
+        .expect("Signed transaction was not delivered")
+        .unwrap();
+    let signed = grid_gossip::message::SignedPayload::decode(&envelope.payload).unwrap();
+
+    assert!(published_forged);
+    assert_eq!(signed.body, b"signed tx".to_vec());
+    assert!(received.try_recv().is_err());
+}
+
+
+#[async_std::test]
+async fn test_peer_sending_invalid_messages_is_banned() {
+    let port = free_tcp_port();
+    let spammer_key = identity::Keypair::generate_ed25519();
+    let spammer_id = PeerId::from(spammer_key.public());
+    let spammer = Node::new(spammer_key, local_config(port, Vec::new())).unwrap();
+    let spammer_handle = spammer.handle();
+    async_std::task::spawn(spammer.run());
+
+    let ban_list_path = std::env::temp_dir().join(format!("grid_gossip_test_{}.bans", std::process::id()));
+    let mut config = local_config(free_tcp_port(), vec![format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap()]);
+    config.scoring.check_interval = Duration::from_millis(100);
+    config.ban_list_path = Some(ban_list_path.clone());
+    let victim = Node::new(identity::Keypair::generate_ed25519(), config).unwrap();
+    let victim_handle = victim.handle();
+    async_std::task::spawn(victim.run());
+
+    // Every unsigned transaction is rejected by the victim and counts against the spammer
+    let mut banned = false;
+    for attempt in 0..100u32 {
+        let _ = spammer_handle.publish(MessageKind::Transaction, attempt.to_be_bytes().to_vec()).await;
+        if victim_handle.banned_peers().await.unwrap().contains(&spammer_id) {
+            banned = true;
+            break;
+        }
+        async_std::task::sleep(Duration::from_millis(50)).await;
+    }
+
+    let mut disconnected = false;
+    for _ in 0..20 {
+        let peer = victim_handle.peer(&spammer_id).await.unwrap();
+        if peer.is_some_and(|peer| !peer.is_connected()) {
+            disconnected = true;
+            break;
+        }
+        async_std::task::sleep(Duration::from_millis(50)).await;
+    }
+    let persisted = BanList::load(Some(ban_list_path.clone())).unwrap();
+
+    std::fs::remove_file(&ban_list_path)
+        .expect("Failed to remove ban list.");
+
+    assert!(banned);
+    assert!(disconnected);
+    assert!(persisted.contains(&spammer_id));
+}
@@ -0,0 +1,99 @@
+use warp::{
+    http::StatusCode,
+    filters::body::BodyDeserializeError,
+    reject::{InvalidQuery, LengthRequired, MethodNotAllowed, PayloadTooLarge, UnsupportedMediaType},
+    Reply, Rejection,
+};
+use log::{debug, error};
+use serde::{Serialize, Deserialize};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+lazy_static::lazy_static! {
+    /// Per-process prefix so request ids stay unique across restarts.
+    static ref REQUEST_ID_PREFIX: u32 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos() ^ duration.as_secs() as u32)
+        .unwrap_or(0);
+}
+
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+/// Body of every error response returned by the API.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub code: u16,
+    pub message: String,
+    pub request_id: String,
+}
+
+#[derive(Debug)]
+pub struct CustomRejection {
+    pub message: String,
+    pub status_code: StatusCode,
+}
+
+impl warp::reject::Reject for CustomRejection {}
+
+
+/// Recovery filter for `routes()`: turns every rejection into a JSON
+/// `ErrorBody` with a matching status and an `x-request-id` header.
+pub async fn handle_rejection(rejection: Rejection) -> Result<impl Reply, Infallible> {
+    let (status_code, message) = if rejection.is_not_found() {
+        (StatusCode::NOT_FOUND, "Route not found".to_string())
+    } else if let Some(custom) = rejection.find::<CustomRejection>() {
+        (custom.status_code, custom.message.clone())
+    } else if let Some(e) = rejection.find::<BodyDeserializeError>() {
+        (StatusCode::BAD_REQUEST, format!("Invalid request body: {}", e))
+    } else if rejection.find::<InvalidQuery>().is_some() {
+        (StatusCode::BAD_REQUEST, "Invalid query string".to_string())
+    } else if rejection.find::<PayloadTooLarge>().is_some() {
+        (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large".to_string())
+    } else if rejection.find::<LengthRequired>().is_some() {
+        (StatusCode::LENGTH_REQUIRED, "Content-Length header required".to_string())
+    } else if rejection.find::<UnsupportedMediaType>().is_some() {
+        (StatusCode::UNSUPPORTED_MEDIA_TYPE, "Content-Type must be application/json".to_string())
+    } else if rejection.find::<MethodNotAllowed>().is_some() {
+        (StatusCode::METHOD_NOT_ALLOWED, "Method not allowed".to_string())
+    } else {
+        error!("API: Unhandled rejection: {:?}", rejection);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
+    };
+
+    let request_id = next_request_id();
+    if status_code.is_server_error() {
+        error!("API: [{}] {}: {}", request_id, status_code, message);
+    } else {
+        debug!("API: [{}] {}: {}", request_id, status_code, message);
+    }
+
+    let body = warp::reply::json(&ErrorBody {
+        code: status_code.as_u16(),
+        message,
+        request_id: request_id.clone(),
+    });
+    let reply = warp::reply::with_header(body, "x-request-id", request_id);
+
+    Ok(warp::reply::with_status(reply, status_code))
+}
+
+
+fn next_request_id() -> String {
+    let count = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:08x}-{:08x}", *REQUEST_ID_PREFIX, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::error::next_request_id;
+
+    #[test]
+    fn test_request_ids_are_unique() {
+        let first = next_request_id();
+        let second = next_request_id();
+
+        assert_ne!(first, second);
+        assert_eq!(first.len(), 17);
+    }
+}
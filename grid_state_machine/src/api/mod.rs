@@ -1,47 +1,137 @@
+mod error;
 mod routes;
 
-use std::sync::Arc;
 use std::error::Error;
-use crate::repository::Repository;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use crate::mempool::Mempool;
+use crate::repository::blocking::AsyncRepository;
 
 pub async fn start_server(
-    arc_repository: Arc<Repository>
+    repository: AsyncRepository,
+    mempool: Arc<Mempool>,
+    addr: SocketAddr,
 ) -> Result<(), Box<dyn Error>> {
-    let routes = routes::routes(arc_repository);
+    let routes = routes::routes(repository, mempool);
 
-    let addr = ([192, 168, 1, 203], 3690); //let addr = ([127, 0, 0, 1], 3690);
-    let ip = format!("{}.{}.{}.{}", addr.0[0], addr.0[1], addr.0[2], addr.0[3]);
+    let (bound_addr, server) = warp::serve(routes)
+        .try_bind_ephemeral(addr)
+        .map_err(|e| format!("Server failed to start on {}: {}", addr, e))?;
 
     println!();
     println!("<***/***>");
     println!("You are connected to THE GRID.");
-    println!("ZERO Node Live :: http://{}:{}", ip, addr.1);
+    println!("ZERO Node Live :: http://{}", bound_addr);
     println!("<***/***>");
 
-    warp::serve(routes).run(addr).await;
+    server.await;
 
-    Err("Server failed to start.".into())
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
+    use std::net::SocketAddr;
+    use std::time::{Duration, Instant};
     use crate::repository::Repository;
+    use crate::repository::blocking::AsyncRepository;
     use crate::db::DatabaseState;
+    use crate::db::store::Durability;
+    use crate::db::memory::MemoryStore;
+    use crate::db::testing::DirGuard;
+    use crate::ledger::producer::run_producer;
+    use crate::mempool::{Mempool, MempoolConfig, NonceGapPolicy};
     use crate::api::{start_server};
-    use std::future::Future;
+    use crate::api::routes::routes;
+    use crate::transaction::testing::signed;
+
+    fn init_repository() -> AsyncRepository {
+        AsyncRepository::new(Arc::new(Repository::new(MemoryStore::new())), 4, Duration::from_secs(5))
+    }
 
-    fn init_repository() -> Arc<Repository> {
-        let db_path: String = "./test_db_api".to_string();
-        let db_state: DatabaseState = DatabaseState::init(db_path);
-        let arc_repository = Arc::new(Repository::new(db_state));
-        arc_repository
+    fn init_mempool() -> Arc<Mempool> {
+        Arc::new(Mempool::new(MempoolConfig {
+            capacity: 10_000,
+            per_sender: 64,
+            ttl: Duration::from_secs(60),
+            nonce_gap: NonceGapPolicy::Reject,
+        }))
     }
 
     #[tokio::test]
-    async fn test_start_server() {
+    async fn test_start_server_reports_bind_failure() {
         let repository = init_repository();
-        let server_fut = start_server(repository);
-        // ToDo: Add assertion logic here
+
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = taken.local_addr().unwrap();
+        let result = start_server(repository, init_mempool(), addr).await;
+
+        assert!(result.is_err());
+    }
+
+
+    /// Load benchmark: concurrent posts and reads against LevelDB with
+    /// synced writes and a running block producer, reporting latency percentiles. Run it with
+    /// `cargo test --release bench_concurrent_requests -- --ignored --nocapture`.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[ignore]
+    async fn bench_concurrent_requests() {
+        const CLIENTS: usize = 64;
+        const REQUESTS_PER_CLIENT: usize = 50;
+
+        let _guard = DirGuard("./test_db_bench".to_string());
+        let db_state = DatabaseState::open("./test_db_bench".to_string(), Durability::Sync).unwrap();
+        let repository = AsyncRepository::new(Arc::new(Repository::new(db_state)), 32, Duration::from_secs(5));
+        let mempool = init_mempool();
+        let producer = tokio::spawn(run_producer(Arc::clone(&mempool), repository.clone(), Duration::from_millis(10)));
+        let route = routes(repository, mempool);
+
+        let started = Instant::now();
+        let clients: Vec<_> = (0..CLIENTS).map(|client| {
+            let route = route.clone();
+            tokio::spawn(async move {
+                let mut latencies = Vec::with_capacity(REQUESTS_PER_CLIENT);
+                for request in 0..REQUESTS_PER_CLIENT {
+                    let sent = Instant::now();
+                    let response = if request % 2 == 0 {
+                        warp::test::request()
+                            .method("POST")
+                            .path("/transaction/post")
+                            .json(&signed(client as u8 + 1, (request / 2) as u64, &format!("client {} request {}", client, request)))
+                            .reply(&route)
+                            .await
+                    } else {
+                        warp::test::request()
+                            .method("GET")
+                            .path(&format!("/transaction/get/{}", client + 1))
+                            .reply(&route)
+                            .await
+                    };
+                    latencies.push(sent.elapsed());
+                    assert!(response.status().is_success() || response.status() == 404, "request failed: {}", response.status());
+                }
+                latencies
+            })
+        }).collect();
+
+        let mut latencies = Vec::with_capacity(CLIENTS * REQUESTS_PER_CLIENT);
+        for client in clients {
+            latencies.extend(client.await.unwrap());
+        }
+        let elapsed = started.elapsed();
+        producer.abort();
+        latencies.sort();
+
+        let percentile = |p: usize| latencies[(latencies.len() * p / 100).min(latencies.len() - 1)];
+        println!(
+            "Bench: {} requests in {:?} ({:.0} req/s), p50 {:?}, p99 {:?}, max {:?}",
+            latencies.len(),
+            elapsed,
+            latencies.len() as f64 / elapsed.as_secs_f64(),
+            percentile(50),
+            percentile(99),
+            latencies[latencies.len() - 1],
+        );
     }
 }
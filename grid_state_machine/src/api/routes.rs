@@ -2,120 +2,548 @@ use warp::{
     http::StatusCode,
     Filter, Reply, Rejection,
 };
-use crate::repository::Repository;
+use crate::db::store::Direction;
+use crate::ledger::BlockId;
+use crate::mempool::{DroppedTransaction, Mempool, MempoolError, PendingTransaction};
+use crate::repository::{CorruptRecord, Repository, RepositoryError, VersionedTransaction};
+use crate::repository::blocking::AsyncRepository;
+use crate::state::{ProofBlock, StatePath, StateQuery, StateValue};
+use crate::state::smt::StateProof;
+use crate::transaction::{Transaction, TransactionError};
+use crate::api::error::{handle_rejection, CustomRejection};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
 use std::sync::Arc;
+use log::debug;
 use serde::{Serialize, Deserialize};
-use serde_json::Error;
-use rand::Rng;
 
+/// Reply (202) to `POST /transaction/post`: the transaction is pending in
+/// the mempool and gets a key once a block includes it.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct Transaction {
-    data: String,
+pub struct AcceptedTransaction {
+    sender: String,
+    nonce: u64,
 }
 
-#[derive(Debug)]
-struct CustomRejection {
-    message: String,
-    status_code: StatusCode,
+/// Reply (202) to `POST /transaction/batch`; every item is pending.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcceptedBatch {
+    accepted: usize,
 }
 
-impl warp::reject::Reject for CustomRejection {}
+/// Reply to `GET /mempool`, highest priority first.
+#[derive(Debug, Serialize)]
+pub struct MempoolContents {
+    count: usize,
+    capacity: usize,
+    transactions: Vec<PendingTransaction>,
+}
 
-impl CustomRejection {
-    fn message(&self) -> String {
-        format!("Status Code: {}: {}", self.status_code, self.message)
-    }
+/// Reply to `GET /account/{pubkey}/nonce`. `next_nonce` counts the
+/// sender's pending transactions that follow on from `committed_nonce`;
+/// `pending` also lists those held behind a gap. `dropped` lists recent
+/// transactions that were accepted but left the mempool uncommitted.
+#[derive(Debug, Serialize)]
+pub struct AccountNonceReply {
+    sender: String,
+    next_nonce: u64,
+    committed_nonce: u64,
+    pending: Vec<u64>,
+    dropped: Vec<DroppedTransaction>,
+}
+
+/// Query string of the read routes. `prove=true` asks for a proof against
+/// the latest block's state root; routes that do not read the state
+/// refuse it.
+#[derive(Debug, Deserialize)]
+pub struct ProveQuery {
+    #[serde(default)]
+    prove: bool,
+}
+
+/// A reply, plus its proof and the block it was proven at when
+/// `prove=true` was asked for. A proven read of a missing or deleted record
+/// has no value, only the proof of its absence.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Proven<T> {
+    #[serde(flatten)]
+    value: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proof: Option<StateProof>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    block: Option<ProofBlock>,
 }
 
+/// Query string of `GET /transaction/list`.
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    after: Option<i32>,
+    limit: Option<usize>,
+    #[serde(default)]
+    order: ListOrder,
+    #[serde(default)]
+    prove: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListedTransaction {
+    key: i32,
+    #[serde(flatten)]
+    transaction: VersionedTransaction,
+}
+
+/// Body of `PUT /transaction/{key}`: the replacement transaction plus the
+/// version the caller last read.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateRequest {
+    expected_version: u64,
+    #[serde(flatten)]
+    transaction: Transaction,
+}
+
+/// Query string of `DELETE /transaction/{key}`.
+#[derive(Debug, Deserialize)]
+pub struct DeleteQuery {
+    expected_version: u64,
+}
+
+/// Reply to updates and deletes: the record's new version. `durability` is
+/// the guarantee the write was acknowledged under: "sync", "group-commit",
+/// "async" or "volatile".
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordVersion {
+    key: i32,
+    version: u64,
+    durability: String,
+}
+
+/// One page of `GET /transaction/list`. Pass `next` back as `after` to
+/// fetch the following page; it is `null` on the last page. `skipped`
+/// lists the keys on the page whose records could not be decoded. With
+/// `prove=true`, `block` is the block every item is proven at.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionList {
+    transactions: Vec<Proven<ListedTransaction>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    skipped: Vec<CorruptRecord>,
+    next: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    block: Option<ProofBlock>,
+}
+
+/// Largest request body accepted by the POST routes.
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+/// Limits for `POST /transaction/batch`, sized for bulk ingestion.
+const MAX_BATCH_BODY_BYTES: u64 = 16 * 1024 * 1024;
+const MAX_BATCH_LEN: usize = 10_000;
+
+const DEFAULT_LIST_LIMIT: usize = 100;
+const MAX_LIST_LIMIT: usize = 1000;
+
 
 pub fn routes(
-    arc_repository: Arc<Repository>
-) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    repository: AsyncRepository,
+    mempool: Arc<Mempool>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
     let route_get_transaction = warp::path("transaction")
         .and(warp::path("get"))
         .and(warp::path::param::<i32>())
-        .and(handle_repository_injection(Arc::clone(&arc_repository)))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<ProveQuery>())
+        .and(handle_repository_injection(repository.clone()))
         .and_then(handle_get_transaction);
 
     let route_post_transaction = warp::path("transaction")
         .and(warp::path("post"))
+        .and(warp::path::end())
         .and(warp::post())
-        .and(handle_repository_injection(Arc::clone(&arc_repository)))
+        .and(handle_repository_injection(repository.clone()))
+        .and(handle_mempool_injection(Arc::clone(&mempool)))
+        .and(warp::body::content_length_limit(MAX_BODY_BYTES))
         .and(warp::body::json())
         .and_then(handle_post_transaction);
 
-    let routes = 
-        route_get_transaction
-        .or(route_post_transaction);
+    let route_post_batch = warp::path("transaction")
+        .and(warp::path("batch"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(handle_repository_injection(repository.clone()))
+        .and(handle_mempool_injection(Arc::clone(&mempool)))
+        .and(warp::body::content_length_limit(MAX_BATCH_BODY_BYTES))
+        .and(warp::body::json())
+        .and_then(handle_post_batch);
+
+    let route_put_transaction = warp::path("transaction")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(handle_repository_injection(repository.clone()))
+        .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and_then(handle_put_transaction);
+
+    let route_delete_transaction = warp::path("transaction")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(warp::query::<DeleteQuery>())
+        .and(handle_repository_injection(repository.clone()))
+        .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and_then(handle_delete_transaction);
+
+    let route_account_nonce = warp::path("account")
+        .and(warp::path::param::<String>())
+        .and(warp::path("nonce"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<ProveQuery>())
+        .and(handle_repository_injection(repository.clone()))
+        .and(handle_mempool_injection(Arc::clone(&mempool)))
+        .and_then(handle_account_nonce);
+
+    let route_get_block = warp::path("block")
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<ProveQuery>())
+        .and(handle_repository_injection(repository.clone()))
+        .and_then(handle_get_block);
 
-    routes
+    let route_mempool = warp::path("mempool")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<ProveQuery>())
+        .and(handle_mempool_injection(mempool))
+        .and_then(handle_get_mempool);
+
+    let route_list_transactions = warp::path("transaction")
+        .and(warp::path("list"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<ListQuery>())
+        .and(handle_repository_injection(repository.clone()))
+        .and_then(handle_list_transactions);
+
+    route_get_transaction
+        .or(route_post_transaction)
+        .or(route_post_batch)
+        .or(route_list_transactions)
+        .or(route_put_transaction)
+        .or(route_delete_transaction)
+        .or(route_account_nonce)
+        .or(route_mempool)
+        .or(route_get_block)
+        .recover(handle_rejection)
 }
 
 
 pub async fn handle_get_transaction(
     key: i32, 
-    arc_repository: Arc<Repository>
+    query: ProveQuery,
+    repository: AsyncRepository
 ) -> Result<impl Reply, Rejection> {
-    let return_value = arc_repository.get_transaction(&key);
-    
-    match return_value {
-        Ok(transaction_bytes) => {
-            let transaction_json = bytes_to_json(transaction_bytes).unwrap();
-
-            println!("Success: Request received and fulfilled");
-            println!("JSON: {}", transaction_json);
-
-            Ok(warp::reply::with_status(transaction_json, StatusCode::OK))
-        },
-        Err(e) => {      
-            let rejection = handle_custom_rejection
-                (e, "Object not inserted", StatusCode::NOT_FOUND);
-            let _custom_rejection_message = rejection.message();
-            
-            Err(warp::reject::custom(rejection))
-        }
-    }
+    let transaction = repository.run(move |repository| read_state(repository, StatePath::Transaction(key), query.prove))
+        .await
+        .map_err(|e| warp::reject::custom(repository_rejection(e)))?;
+
+    let transaction_json = serde_json::to_string_pretty(&transaction)
+        .map_err(|e| warp::reject::custom(handle_custom_rejection(
+            e.to_string(), "Failed to encode transaction", StatusCode::INTERNAL_SERVER_ERROR
+        )))?;
+
+    debug!("API: Key {} read", key);
+
+    Ok(warp::reply::with_status(transaction_json, StatusCode::OK))
 }
 
 
 pub async fn handle_post_transaction(
-    arc_repository: Arc<Repository>, 
+    repository: AsyncRepository, 
+    mempool: Arc<Mempool>,
     transaction: Transaction
 ) -> Result<impl Reply, Rejection> {    
-    let key = generate_random_index(1, 100000000);
+    transaction.verify()
+        .map_err(|e| warp::reject::custom(transaction_rejection(e)))?;
 
-    println!("API: Key used: {}", key);
+    let committed = committed_nonce(&repository, transaction.sender.clone()).await?;
 
-    let transaction_string = serde_json::to_string(&transaction).unwrap();
-    let transaction_bytes = transaction_string.as_bytes().to_vec();
+    let body = AcceptedTransaction { sender: transaction.sender.clone(), nonce: transaction.nonce };
+    mempool.submit(transaction, committed)
+        .map_err(|e| warp::reject::custom(mempool_rejection(e)))?;
 
-    let return_value = arc_repository.add_transaction(&key, transaction_bytes);
+    debug!("API: Nonce {} of {} pending", body.nonce, body.sender);
 
-    match return_value {
-        Ok(()) => Ok(warp::reply::with_status("Succes", StatusCode::OK)),
-        Err(e) => {
-            let rejection = handle_custom_rejection
-                (e, "Object not found", StatusCode::NO_CONTENT);
-            let _custom_rejection_message = rejection.message();
- 
-            Err(warp::reject::custom(rejection))
-        }
+    Ok(warp::reply::with_status(warp::reply::json(&body), StatusCode::ACCEPTED))
+}
+
+
+/// Admits the whole batch to the mempool or none of it.
+pub async fn handle_post_batch(
+    repository: AsyncRepository,
+    mempool: Arc<Mempool>,
+    transactions: Vec<Transaction>
+) -> Result<impl Reply, Rejection> {
+    if transactions.is_empty() || transactions.len() > MAX_BATCH_LEN {
+        return Err(warp::reject::custom(handle_custom_rejection(
+            format!("API: Invalid batch size {}", transactions.len()),
+            &format!("batch must hold between 1 and {} transactions", MAX_BATCH_LEN),
+            StatusCode::BAD_REQUEST,
+        )));
     }
+
+    // Checking up to MAX_BATCH_LEN signatures is CPU-bound, so it runs on
+    // the blocking pool along with the nonce lookups.
+    let (transactions, committed) = repository
+        .run(move |repository| {
+            let invalid = transactions.iter().enumerate()
+                .find_map(|(index, transaction)| transaction.verify().err().map(|e| (index, e)));
+            if let Some(invalid) = invalid {
+                return Ok(Err(invalid));
+            }
+
+            let senders: BTreeSet<String> = transactions.iter()
+                .map(|transaction| transaction.sender.clone())
+                .collect();
+            let committed = senders.into_iter()
+                .map(|sender| repository.next_nonce(&sender).map(|nonce| (sender, nonce)))
+                .collect::<Result<BTreeMap<String, u64>, RepositoryError>>()?;
+            Ok(Ok((transactions, committed)))
+        })
+        .await
+        .map_err(|e| warp::reject::custom(repository_rejection(e)))?
+        .map_err(|(index, e)| warp::reject::custom(handle_custom_rejection(
+            e.to_string(),
+            &format!("Batch item {}: {}", index, transaction_error_message(&e)),
+            StatusCode::BAD_REQUEST,
+        )))?;
+
+    let accepted = transactions.len();
+    mempool.submit_all(transactions, &committed).map_err(|(index, e)| {
+        let (message, status_code) = mempool_error_reply(&e);
+        warp::reject::custom(handle_custom_rejection(
+            e.to_string(), &format!("Batch item {}: {}", index, message), status_code,
+        ))
+    })?;
+
+    debug!("API: Batch of {} pending", accepted);
+
+    Ok(warp::reply::with_status(warp::reply::json(&AcceptedBatch { accepted }), StatusCode::ACCEPTED))
+}
+
+
+pub async fn handle_put_transaction(
+    key: i32,
+    repository: AsyncRepository,
+    request: UpdateRequest
+) -> Result<impl Reply, Rejection> {
+    request.transaction.verify()
+        .map_err(|e| warp::reject::custom(transaction_rejection(e)))?;
+
+    let version = repository
+        .run(move |repository| repository.update_transaction(&key, request.expected_version, &request.transaction))
+        .await
+        .map_err(|e| warp::reject::custom(repository_rejection(e)))?;
+
+    debug!("API: Key {} updated to version {}", key, version);
+
+    let durability = repository.durability().name().to_string();
+    let body = warp::reply::json(&RecordVersion { key, version, durability });
+    Ok(warp::reply::with_status(body, StatusCode::OK))
+}
+
+
+/// The body is a transaction signed by the record's sender with their next
+/// nonce, which the delete consumes.
+pub async fn handle_delete_transaction(
+    key: i32,
+    query: DeleteQuery,
+    repository: AsyncRepository,
+    transaction: Transaction
+) -> Result<impl Reply, Rejection> {
+    transaction.verify()
+        .map_err(|e| warp::reject::custom(transaction_rejection(e)))?;
+
+    let version = repository
+        .run(move |repository| repository.delete_transaction(&key, query.expected_version, &transaction))
+        .await
+        .map_err(|e| warp::reject::custom(repository_rejection(e)))?;
+
+    debug!("API: Key {} deleted at version {}", key, version);
+
+    let durability = repository.durability().name().to_string();
+    let body = warp::reply::json(&RecordVersion { key, version, durability });
+    Ok(warp::reply::with_status(body, StatusCode::OK))
+}
+
+
+pub async fn handle_list_transactions(
+    query: ListQuery,
+    repository: AsyncRepository
+) -> Result<impl Reply, Rejection> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+    if limit == 0 || limit > MAX_LIST_LIMIT {
+        return Err(warp::reject::custom(handle_custom_rejection(
+            format!("API: Invalid list limit {}", limit),
+            &format!("limit must be between 1 and {}", MAX_LIST_LIMIT),
+            StatusCode::BAD_REQUEST,
+        )));
+    }
+    let direction = match query.order {
+        ListOrder::Asc => Direction::Forward,
+        ListOrder::Desc => Direction::Reverse,
+    };
+
+    let (page, proofs, block) = repository
+        .run(move |repository| if query.prove {
+            repository.list_proven_transactions(query.after, limit, direction)
+                .map(|(page, proofs, block)| (page, proofs.into_iter().map(Some).collect(), Some(block)))
+        } else {
+            repository.list_transactions(query.after, limit, direction)
+                .map(|page| (page, Vec::new(), None))
+        })
+        .await
+        .map_err(|e| warp::reject::custom(repository_rejection(e)))?;
+
+    let proofs = proofs.into_iter().chain(std::iter::repeat_with(|| None));
+    let body = TransactionList {
+        transactions: page.transactions.into_iter()
+            .zip(proofs)
+            .map(|((key, transaction), proof)| Proven { value: ListedTransaction { key, transaction }, proof, block: None })
+            .collect(),
+        skipped: page.skipped,
+        next: page.next,
+        block,
+    };
+
+    Ok(warp::reply::with_status(warp::reply::json(&body), StatusCode::OK))
+}
+
+
+pub async fn handle_account_nonce(
+    sender: String,
+    query: ProveQuery,
+    repository: AsyncRepository,
+    mempool: Arc<Mempool>
+) -> Result<impl Reply, Rejection> {
+    let lookup = sender.clone();
+    let committed = repository.run(move |repository| read_state(repository, StatePath::Nonce(lookup), query.prove))
+        .await
+        .map_err(|e| warp::reject::custom(repository_rejection(e)))?;
+    let committed_nonce = committed.value.map_or(0, nonce_value);
+
+    let pending: Vec<u64> = mempool.pending_nonces(&sender).into_iter()
+        .filter(|nonce| *nonce >= committed_nonce)
+        .collect();
+    let mut next_nonce = committed_nonce;
+    while pending.binary_search(&next_nonce).is_ok() {
+        next_nonce += 1;
+    }
+
+    let dropped = mempool.dropped(&sender);
+    let body = Proven {
+        value: AccountNonceReply { sender, next_nonce, committed_nonce, pending, dropped },
+        proof: committed.proof,
+        block: committed.block,
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&body), StatusCode::OK))
+}
+
+
+/// Next nonce of `sender` in the committed state.
+async fn committed_nonce(repository: &AsyncRepository, sender: String) -> Result<u64, Rejection> {
+    let value = repository.run(move |repository| repository.query(&StatePath::Nonce(sender)))
+        .await
+        .map_err(|e| warp::reject::custom(repository_rejection(e)))?;
+
+    Ok(nonce_value(value))
+}
+
+
+fn nonce_value(value: StateValue) -> u64 {
+    match value {
+        StateValue::Nonce(nonce) => nonce,
+        StateValue::Transaction(_) | StateValue::Legacy(_) => unreachable!("nonce paths hold nonces"),
+    }
+}
+
+
+/// Reads `path` from the committed state, with its proof when `prove` is set.
+/// Only a proven read can come back without a value.
+fn read_state(repository: &Repository, path: StatePath, prove: bool) -> Result<Proven<Option<StateValue>>, RepositoryError> {
+    if prove {
+        repository.prove(&path).map(|read| Proven { value: read.value, proof: Some(read.proof), block: Some(read.block) })
+    } else {
+        repository.query(&path).map(|value| Proven { value: Some(value), proof: None, block: None })
+    }
+}
+
+
+/// `GET /block/{height}`, `GET /block/{hash}` and `GET /block/latest`.
+pub async fn handle_get_block(
+    id: String,
+    query: ProveQuery,
+    repository: AsyncRepository
+) -> Result<impl Reply, Rejection> {
+    if query.prove {
+        return Err(unprovable("Blocks are not part of the state; verify reads against a block's state_root"));
+    }
+    let id: BlockId = id.parse()
+        .map_err(|e| warp::reject::custom(handle_custom_rejection(
+            e, "Block id must be a height, a 64-hex hash or latest", StatusCode::BAD_REQUEST
+        )))?;
+    let block = repository.run(move |repository| repository.find_block(&id))
+        .await
+        .map_err(|e| warp::reject::custom(repository_rejection(e)))?;
+
+    Ok(warp::reply::with_status(warp::reply::json(&block), StatusCode::OK))
+}
+
+
+pub async fn handle_get_mempool(
+    query: ProveQuery,
+    mempool: Arc<Mempool>
+) -> Result<impl Reply, Rejection> {
+    if query.prove {
+        return Err(unprovable("Pending transactions are not part of the committed state"));
+    }
+    let transactions = mempool.pending();
+    let body = MempoolContents { count: transactions.len(), capacity: mempool.capacity(), transactions };
+
+    Ok(warp::reply::with_status(warp::reply::json(&body), StatusCode::OK))
 }
 
 
 fn handle_repository_injection(
-    arc_repository: Arc<Repository>
+    repository: AsyncRepository
+) -> impl Filter<Extract = (
+        AsyncRepository,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || repository.clone())
+}
+
+
+fn handle_mempool_injection(
+    mempool: Arc<Mempool>
 ) -> impl Filter<Extract = (
-        Arc<Repository>,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || Arc::clone(&arc_repository))
+        Arc<Mempool>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || Arc::clone(&mempool))
 }
 
 
 fn handle_custom_rejection(
     error_msg: String, message: &str, status_code: StatusCode
 ) -> CustomRejection {
-    eprintln!("Error: {}", error_msg);
+    debug!("API: {}", error_msg);
     CustomRejection {
         message: message.to_string(),
         status_code
@@ -123,51 +551,680 @@ fn handle_custom_rejection(
 }
 
 
-fn bytes_to_json(bytes: Vec<u8>) -> Result<String, Error> {
-    let input_bytes: Transaction = serde_json::from_slice(&bytes)
-        .expect("Failed to deserialize Vec<u8> into data structure");
-    let output_json = serde_json::to_string_pretty(&input_bytes)
-        .expect("Failed to serialize to human-readable JSON");
-    Ok(output_json)
+fn unprovable(message: &str) -> Rejection {
+    warp::reject::custom(handle_custom_rejection(
+        "API: Proof asked for outside the state".to_string(), message, StatusCode::BAD_REQUEST
+    ))
 }
 
-fn generate_random_index(min: i32, max: i32) -> i32 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..=max)
+
+fn transaction_rejection(error: TransactionError) -> CustomRejection {
+    handle_custom_rejection(error.to_string(), transaction_error_message(&error), StatusCode::BAD_REQUEST)
+}
+
+
+fn transaction_error_message(error: &TransactionError) -> &'static str {
+    match error {
+        TransactionError::Sender(_) => "Invalid sender key",
+        TransactionError::KeyType => "Sender key must be ed25519",
+        TransactionError::Signature => "Invalid transaction signature",
+    }
+}
+
+
+fn mempool_rejection(error: MempoolError) -> CustomRejection {
+    let (message, status_code) = mempool_error_reply(&error);
+    handle_custom_rejection(error.to_string(), &message, status_code)
+}
+
+
+fn mempool_error_reply(error: &MempoolError) -> (String, StatusCode) {
+    match error {
+        MempoolError::Stale { nonce, expected } => (
+            format!("Nonce {} is already used; next nonce is {}", nonce, expected),
+            StatusCode::CONFLICT,
+        ),
+        MempoolError::Duplicate { nonce, fee } => (
+            format!("Nonce {} is already pending with fee {}", nonce, fee),
+            StatusCode::CONFLICT,
+        ),
+        MempoolError::Gap { nonce, expected } => (
+            format!("Nonce {} skips ahead; next nonce is {}", nonce, expected),
+            StatusCode::CONFLICT,
+        ),
+        MempoolError::SenderFull { limit } => (
+            format!("Sender already has {} pending transactions", limit),
+            StatusCode::TOO_MANY_REQUESTS,
+        ),
+        MempoolError::Full => ("Mempool is full, retry later".to_string(), StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+
+fn repository_rejection(error: RepositoryError) -> CustomRejection {
+    let (message, status_code) = match &error {
+        RepositoryError::NotFound(_) => ("Object not found".to_string(), StatusCode::NOT_FOUND),
+        RepositoryError::Deleted(key) => (format!("Transaction {} was deleted", key), StatusCode::GONE),
+        RepositoryError::VersionMismatch { key, expected, actual } => (
+            format!("Transaction {} is at version {}, expected {}", key, actual, expected),
+            StatusCode::CONFLICT,
+        ),
+        RepositoryError::Corrupt { key, .. } => (
+            format!("Stored record {} could not be decoded", key),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+        RepositoryError::InvalidSender(_) => ("Invalid sender key".to_string(), StatusCode::BAD_REQUEST),
+        RepositoryError::StaleNonce { nonce, expected, .. } => (
+            format!("Nonce {} is already used; next nonce is {}", nonce, expected),
+            StatusCode::CONFLICT,
+        ),
+        RepositoryError::NonceGap { nonce, expected, .. } => (
+            format!("Nonce {} skips ahead; next nonce is {}", nonce, expected),
+            StatusCode::CONFLICT,
+        ),
+        RepositoryError::SenderMismatch(key) => (
+            format!("Transaction {} belongs to another sender", key),
+            StatusCode::FORBIDDEN,
+        ),
+        RepositoryError::Unsigned(key) => (
+            format!("Transaction {} is an unsigned legacy record and cannot be changed", key),
+            StatusCode::CONFLICT,
+        ),
+        RepositoryError::BlockNotFound(_) => ("Block not found".to_string(), StatusCode::NOT_FOUND),
+        RepositoryError::CorruptBlock { height, .. } => (
+            format!("Stored block {} could not be decoded", height),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+        RepositoryError::Busy => ("Storage is busy, retry later".to_string(), StatusCode::SERVICE_UNAVAILABLE),
+        _ => ("Storage error".to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+    };
+
+    handle_custom_rejection(error.to_string(), &message, status_code)
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
-    use std::error::Error;
-    use warp::http::StatusCode;
-    use warp::Rejection;
     use crate::Repository;
-    use crate::db::{DatabaseState};
+    use crate::db::memory::MemoryStore;
     use crate::api::routes::routes;
+    use crate::api::error::ErrorBody;
+    use crate::api::routes::TransactionList;
+    use crate::repository::blocking::AsyncRepository;
+    use crate::mempool::{Mempool, MempoolConfig, NonceGapPolicy};
+    use crate::ledger::producer::produce_block;
+    use crate::ledger::BlockId;
+    use crate::repository::RepositoryError;
+    use crate::state::ProofBlock;
+    use crate::state::smt::{verify_proof, StateProof};
+    use crate::transaction::StoredRecord;
+    use crate::transaction::testing::{signed, transaction, with_fee};
+    use std::time::Duration;
 
 
     fn init_repository() -> Arc<Repository> {
-        let db_path: String = "./test_db_routing".to_string();
-        let db_state: DatabaseState = <DatabaseState>::init(db_path);
-        let arc_repository = Arc::new(Repository::new(db_state));
-        arc_repository
+        Arc::new(Repository::new(MemoryStore::new()))
+    }
+
+
+    fn init_mempool(nonce_gap: NonceGapPolicy) -> Arc<Mempool> {
+        Arc::new(Mempool::new(MempoolConfig { capacity: 100, per_sender: 10, ttl: Duration::from_secs(60), nonce_gap }))
+    }
+
+
+    fn async_repository(arc_repository: &Arc<Repository>) -> AsyncRepository {
+        AsyncRepository::new(Arc::clone(arc_repository), 4, Duration::from_secs(5))
+    }
+
+
+    #[tokio::test]
+    async fn test_get_transaction() {
+        let arc_repository = init_repository();
+        arc_repository.add_transaction(&123, serde_json::to_vec(&transaction("meow")).unwrap()).unwrap();
+
+        let route = routes(async_repository(&arc_repository), init_mempool(NonceGapPolicy::Reject));
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/transaction/get/123")
+            .reply(&route)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        assert!(String::from_utf8_lossy(response.body()).contains("meow"));
+    }
+
+
+    #[tokio::test]
+    async fn test_post_transaction_is_pending_until_in_a_block() {
+        let arc_repository = init_repository();
+        arc_repository.add_transaction(&2, serde_json::to_vec(&transaction("taken")).unwrap()).unwrap();
+        let repository = async_repository(&arc_repository);
+        let mempool = init_mempool(NonceGapPolicy::Reject);
+        let route = routes(repository.clone(), Arc::clone(&mempool));
+
+        let post = |nonce: u64| warp::test::request()
+            .method("POST")
+            .path("/transaction/post")
+            .json(&signed(1, nonce, "meow"));
+
+        let mut responses = Vec::new();
+        for nonce in [0, 1, 1, 3] {
+            responses.push(post(nonce).reply(&route).await);
+        }
+        let before_commit = arc_repository.get_transaction(&1);
+        let committed = produce_block(&mempool, &repository).await.map(|block| block.map(|block| block.keys));
+        let replayed = post(1).reply(&route).await;
+
+        let accepted: serde_json::Value = serde_json::from_slice(responses[0].body()).unwrap();
+        assert_eq!(responses[0].status(), 202);
+        assert_eq!(accepted, serde_json::json!({ "sender": transaction("").sender, "nonce": 0 }));
+        assert_eq!(responses[1].status(), 202);
+        assert_eq!(responses[2].status(), 409);
+        assert!(String::from_utf8_lossy(responses[2].body()).contains("Nonce 1 is already pending"));
+        assert_eq!(responses[3].status(), 409);
+        assert!(String::from_utf8_lossy(responses[3].body()).contains("Nonce 3 skips ahead; next nonce is 2"));
+        assert_eq!(before_commit, Err(RepositoryError::NotFound(1)));
+        // Key 2 is taken, so the block skips it.
+        assert_eq!(committed, Ok(Some(vec![1, 3])));
+        assert_eq!(replayed.status(), 409);
+        assert!(String::from_utf8_lossy(replayed.body()).contains("Nonce 1 is already used; next nonce is 2"));
+        assert_eq!(
+            arc_repository.get_transaction(&3),
+            Ok(StoredRecord::Live { version: 1, transaction: signed(1, 1, "meow") }.encode().unwrap())
+        );
+    }
+
+
+    #[tokio::test]
+    async fn test_errors_are_json() {
+        let arc_repository = init_repository();
+        let route = routes(async_repository(&arc_repository), init_mempool(NonceGapPolicy::Reject));
+
+        let missing = warp::test::request()
+            .method("GET")
+            .path("/transaction/get/42")
+            .reply(&route)
+            .await;
+        let bad_body = warp::test::request()
+            .method("POST")
+            .path("/transaction/post")
+            .header("content-type", "application/json")
+            .body("{not json")
+            .reply(&route)
+            .await;
+        let wrong_method = warp::test::request()
+            .method("DELETE")
+            .path("/transaction/post")
+            .reply(&route)
+            .await;
+        let too_large = warp::test::request()
+            .method("POST")
+            .path("/transaction/post")
+            .json(&transaction(&"x".repeat(70 * 1024)))
+            .reply(&route)
+            .await;
+
+        for (response, code) in [
+            (missing, 404),
+            (bad_body, 400),
+            (wrong_method, 405),
+            (too_large, 413),
+        ] {
+            assert_eq!(response.status(), code);
+            let body: ErrorBody = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body.code, code);
+            assert!(!body.message.is_empty());
+            assert_eq!(response.headers()["x-request-id"], body.request_id.as_str());
+        }
+    }
+
+
+    #[tokio::test]
+    async fn test_corrupt_record_returns_500() {
+        let arc_repository = init_repository();
+        arc_repository.add_transaction(&77, b"Hello, Meow!".to_vec()).unwrap();
+
+        let route = routes(async_repository(&arc_repository), init_mempool(NonceGapPolicy::Reject));
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/transaction/get/77")
+            .reply(&route)
+            .await;
+
+        assert_eq!(response.status(), 500);
+        let body: ErrorBody = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body.message, "Stored record 77 could not be decoded");
+    }
+
+
+    #[tokio::test]
+    async fn test_list_skips_corrupt_records() {
+        let arc_repository = init_repository();
+        arc_repository.add_transaction(&1, b"Hello, Meow!".to_vec()).unwrap();
+        arc_repository.insert_transaction(&signed(1, 0, "a")).unwrap();
+
+        let route = routes(async_repository(&arc_repository), init_mempool(NonceGapPolicy::Reject));
+
+        let response = warp::test::request()
+            .method("GET")
+            .path("/transaction/list")
+            .reply(&route)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let page: TransactionList = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(page.transactions.iter().map(|listed| listed.value.key).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(page.skipped.iter().map(|record| record.key).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(page.next, None);
+    }
+
+
+    #[tokio::test]
+    async fn test_list_transactions_walks_with_cursor() {
+        let arc_repository = init_repository();
+        for (nonce, data) in ["a", "b", "c"].into_iter().enumerate() {
+            arc_repository.insert_transaction(&signed(1, nonce as u64, data)).unwrap();
+        }
+        let route = routes(async_repository(&arc_repository), init_mempool(NonceGapPolicy::Reject));
+
+        let mut seen = Vec::new();
+        let mut path = "/transaction/list?limit=2".to_string();
+        loop {
+            let response = warp::test::request()
+                .method("GET")
+                .path(&path)
+                .reply(&route)
+                .await;
+            assert_eq!(response.status(), 200);
+
+            let page: TransactionList = serde_json::from_slice(response.body()).unwrap();
+            seen.extend(page.transactions.into_iter().map(|listed| (listed.value.key, listed.value.transaction.transaction.payload)));
+            match page.next {
+                Some(next) => path = format!("/transaction/list?limit=2&after={}", next),
+                None => break,
+            }
+        }
+
+        let newest = warp::test::request()
+            .method("GET")
+            .path("/transaction/list?order=desc&limit=1")
+            .reply(&route)
+            .await;
+        let bad_limit = warp::test::request()
+            .method("GET")
+            .path("/transaction/list?limit=0")
+            .reply(&route)
+            .await;
+        let bad_query = warp::test::request()
+            .method("GET")
+            .path("/transaction/list?after=abc")
+            .reply(&route)
+            .await;
+
+        assert_eq!(seen, vec![(1, "a".to_string()), (2, "b".to_string()), (3, "c".to_string())]);
+        let newest: serde_json::Value = serde_json::from_slice(newest.body()).unwrap();
+        assert_eq!(newest["transactions"][0]["key"], 3);
+        assert_eq!(newest["transactions"][0]["version"], 1);
+        assert_eq!(newest["transactions"][0]["payload"], "c");
+        assert_eq!(newest["next"], 3);
+        assert_eq!(bad_limit.status(), 400);
+        assert_eq!(bad_query.status(), 400);
+    }
+
+
+    #[tokio::test]
+    async fn test_post_batch_is_all_or_nothing() {
+        let arc_repository = init_repository();
+        let repository = async_repository(&arc_repository);
+        let mempool = init_mempool(NonceGapPolicy::Reject);
+        let route = routes(repository.clone(), Arc::clone(&mempool));
+
+        let batch = |body: serde_json::Value| warp::test::request()
+            .method("POST")
+            .path("/transaction/batch")
+            .json(&body);
+
+        let response = batch(serde_json::json!([signed(1, 0, "a"), signed(1, 1, "b"), signed(1, 2, "c")])).reply(&route).await;
+        let duplicate = batch(serde_json::json!([signed(2, 0, "d"), signed(1, 2, "c")])).reply(&route).await;
+        let empty = batch(serde_json::json!([])).reply(&route).await;
+        let malformed = batch(serde_json::json!([transaction("a"), { "nope": 1 }])).reply(&route).await;
+        let pending = mempool.pending().len();
+        let committed = produce_block(&mempool, &repository).await.map(|block| block.map(|block| block.keys));
+
+        assert_eq!(response.status(), 202);
+        assert_eq!(response.body().as_ref(), br#"{"accepted":3}"#);
+        assert_eq!(duplicate.status(), 409);
+        let duplicate: ErrorBody = serde_json::from_slice(duplicate.body()).unwrap();
+        assert_eq!(duplicate.message, "Batch item 1: Nonce 2 is already pending with fee 0");
+        assert_eq!(empty.status(), 400);
+        assert_eq!(malformed.status(), 400);
+        assert_eq!(pending, 3);
+        assert_eq!(committed, Ok(Some(vec![1, 2, 3])));
+        assert_eq!(arc_repository.read_transaction(&3).map(|stored| stored.transaction), Ok(signed(1, 2, "c")));
+        assert!(arc_repository.get_transaction(&4).is_err());
     }
 
 
-    #[test]
-    fn test_get_transaction() {
+    #[tokio::test]
+    async fn test_put_and_delete_use_versions() {
         let arc_repository = init_repository();
-        
-        let route = routes(Arc::clone(&arc_repository));
-        
-        let request = warp::test::request()
+        arc_repository.insert_transaction(&transaction("v1")).unwrap();
+        let route = routes(async_repository(&arc_repository), init_mempool(NonceGapPolicy::Reject));
+
+        let put = |expected_version: u64| warp::test::request()
+            .method("PUT")
+            .path("/transaction/1")
+            .json(&{
+                let mut body = serde_json::to_value(signed(1, 1, "v2")).unwrap();
+                body["expected_version"] = expected_version.into();
+                body
+            });
+
+        let updated = put(1).reply(&route).await;
+        let stale = put(1).reply(&route).await;
+        let read = warp::test::request()
+            .method("GET")
+            .path("/transaction/get/1")
+            .reply(&route)
+            .await;
+        let missing_version = warp::test::request()
+            .method("DELETE")
+            .path("/transaction/1")
+            .json(&signed(1, 2, "delete"))
+            .reply(&route)
+            .await;
+        let unsigned = warp::test::request()
+            .method("DELETE")
+            .path("/transaction/1?expected_version=2")
+            .reply(&route)
+            .await;
+        let forged = warp::test::request()
+            .method("DELETE")
+            .path("/transaction/1?expected_version=2")
+            .json(&{
+                let mut forged = signed(2, 0, "delete");
+                forged.sender = signed(1, 0, "").sender;
+                forged
+            })
+            .reply(&route)
+            .await;
+        let foreign = warp::test::request()
+            .method("DELETE")
+            .path("/transaction/1?expected_version=2")
+            .json(&signed(2, 0, "delete"))
+            .reply(&route)
+            .await;
+        let deleted = warp::test::request()
+            .method("DELETE")
+            .path("/transaction/1?expected_version=2")
+            .json(&signed(1, 2, "delete"))
+            .reply(&route)
+            .await;
+        let gone = warp::test::request()
+            .method("GET")
+            .path("/transaction/get/1")
+            .reply(&route)
+            .await;
+        let missing = warp::test::request()
+            .method("DELETE")
+            .path("/transaction/42?expected_version=1")
+            .json(&signed(1, 3, "delete"))
+            .reply(&route)
+            .await;
+
+        assert_eq!(updated.status(), 200);
+        assert_eq!(updated.body().as_ref(), br#"{"key":1,"version":2,"durability":"volatile"}"#);
+        assert_eq!(stale.status(), 409);
+        let read: serde_json::Value = serde_json::from_slice(read.body()).unwrap();
+        assert_eq!(read["version"], 2);
+        assert_eq!(read["payload"], "v2");
+        assert_eq!(missing_version.status(), 400);
+        assert_eq!(unsigned.status(), 411);
+        assert_eq!(forged.status(), 400);
+        assert_eq!(foreign.status(), 403);
+        assert_eq!(deleted.status(), 200);
+        assert_eq!(deleted.body().as_ref(), br#"{"key":1,"version":3,"durability":"volatile"}"#);
+        assert_eq!(gone.status(), 410);
+        assert_eq!(missing.status(), 404);
+    }
+
+
+    #[tokio::test]
+    async fn test_bad_signatures_are_refused() {
+        let arc_repository = init_repository();
+        let route = routes(async_repository(&arc_repository), init_mempool(NonceGapPolicy::Reject));
+
+        let mut tampered = transaction("transfer 369");
+        tampered.payload = "transfer 963".to_string();
+        let mut unknown_sender = transaction("transfer 369");
+        unknown_sender.sender = "Hello, Meow!".to_string();
+        let other_sender = signed(2, 0, "transfer 369");
+        let mut swapped = transaction("transfer 369");
+        swapped.sender = other_sender.sender;
+
+        let mut responses = Vec::new();
+        for body in [tampered.clone(), unknown_sender, swapped] {
+            let response = warp::test::request()
+                .method("POST")
+                .path("/transaction/post")
+                .json(&body)
+                .reply(&route)
+                .await;
+            responses.push(response);
+        }
+        let batch = warp::test::request()
+            .method("POST")
+            .path("/transaction/batch")
+            .json(&[transaction("a"), tampered])
+            .reply(&route)
+            .await;
+
+        for response in &responses {
+            assert_eq!(response.status(), 400);
+        }
+        let tampered: ErrorBody = serde_json::from_slice(responses[0].body()).unwrap();
+        assert_eq!(tampered.message, "Invalid transaction signature");
+        let batch_body: ErrorBody = serde_json::from_slice(batch.body()).unwrap();
+        assert_eq!(batch.status(), 400);
+        assert_eq!(batch_body.message, "Batch item 1: Invalid transaction signature");
+        assert!(arc_repository.get_transaction(&1).is_err());
+    }
+
+
+    #[tokio::test]
+    async fn test_account_nonce_counts_pending_transactions() {
+        let arc_repository = init_repository();
+        let repository = async_repository(&arc_repository);
+        let mempool = init_mempool(NonceGapPolicy::Hold { max_gap: 4 });
+        let route = routes(repository.clone(), Arc::clone(&mempool));
+        let sender = transaction("").sender;
+
+        let post = |nonce: u64| warp::test::request()
+            .method("POST")
+            .path("/transaction/post")
+            .json(&signed(1, nonce, "pay"));
+        let nonce = || warp::test::request()
             .method("GET")
-            .path("/transaction/get/123");
-        
-        //TODO: Implement rest of test
-            //let response = request.reply(&route);     
-            //println!("res {}", response);
-            //assert_eq!(response.status(), 200);
+            .path(&format!("/account/{}/nonce", sender));
+
+        let held = post(1).reply(&route).await;
+        let before = nonce().reply(&route).await;
+        let filled = post(0).reply(&route).await;
+        let filled_nonce = nonce().reply(&route).await;
+        let committed = produce_block(&mempool, &repository).await.map(|block| block.map(|block| block.keys));
+        let after = nonce().reply(&route).await;
+        let bad_sender = warp::test::request()
+            .method("GET")
+            .path("/account/xyz/nonce")
+            .reply(&route)
+            .await;
+
+        assert_eq!(held.status(), 202);
+        let before: serde_json::Value = serde_json::from_slice(before.body()).unwrap();
+        assert_eq!(
+            before,
+            serde_json::json!({ "sender": sender, "next_nonce": 0, "committed_nonce": 0, "pending": [1], "dropped": [] })
+        );
+        assert_eq!(filled.status(), 202);
+        let filled_nonce: serde_json::Value = serde_json::from_slice(filled_nonce.body()).unwrap();
+        assert_eq!(filled_nonce["next_nonce"], 2);
+        assert_eq!(committed, Ok(Some(vec![1, 2])));
+        let after: serde_json::Value = serde_json::from_slice(after.body()).unwrap();
+        assert_eq!(
+            after,
+            serde_json::json!({ "sender": sender, "next_nonce": 2, "committed_nonce": 2, "pending": [], "dropped": [] })
+        );
+        assert_eq!(bad_sender.status(), 400);
+    }
+
+
+    #[tokio::test]
+    async fn test_get_block_by_height_hash_and_latest() {
+        let arc_repository = init_repository();
+        let repository = async_repository(&arc_repository);
+        let mempool = init_mempool(NonceGapPolicy::Reject);
+        let route = routes(repository.clone(), Arc::clone(&mempool));
+
+        let get = |id: &str| warp::test::request()
+            .method("GET")
+            .path(&format!("/block/{}", id));
+
+        let none_yet = get("latest").reply(&route).await;
+        mempool.submit(signed(1, 0, "a"), 0).unwrap();
+        let first = produce_block(&mempool, &repository).await.unwrap().unwrap();
+        mempool.submit(signed(1, 1, "b"), 1).unwrap();
+        let second = produce_block(&mempool, &repository).await.unwrap().unwrap();
+
+        let latest = get("latest").reply(&route).await;
+        let by_height = get("0").reply(&route).await;
+        let by_hash = get(&first.hash.to_string()).reply(&route).await;
+        let missing = get("7").reply(&route).await;
+        let malformed = get("abc").reply(&route).await;
+        let short_hash = get(&first.hash.to_string()[..63]).reply(&route).await;
+        let bad_hash = get(&"z".repeat(64)).reply(&route).await;
+
+        assert_eq!(none_yet.status(), 404);
+        assert_eq!(latest.status(), 200);
+        let latest: serde_json::Value = serde_json::from_slice(latest.body()).unwrap();
+        assert_eq!(latest["height"], 1);
+        assert_eq!(latest["hash"], second.hash.to_string());
+        assert_eq!(latest["parent_hash"], first.hash.to_string());
+        assert_eq!(latest["transactions"], serde_json::json!([signed(1, 1, "b").hash().unwrap()]));
+        assert_eq!(latest["keys"], serde_json::json!([2]));
+        assert_eq!(by_height.body(), by_hash.body());
+        let by_height: serde_json::Value = serde_json::from_slice(by_height.body()).unwrap();
+        assert_eq!(by_height["hash"], first.hash.to_string());
+        assert_eq!(missing.status(), 404);
+        let missing: ErrorBody = serde_json::from_slice(missing.body()).unwrap();
+        assert_eq!(missing.message, "Block not found");
+        for malformed in [malformed, short_hash, bad_hash] {
+            assert_eq!(malformed.status(), 400);
+            let malformed: ErrorBody = serde_json::from_slice(malformed.body()).unwrap();
+            assert_eq!(malformed.message, "Block id must be a height, a 64-hex hash or latest");
+        }
+    }
+
+
+    #[tokio::test]
+    async fn test_reads_prove_against_the_latest_block() {
+        let arc_repository = init_repository();
+        let repository = async_repository(&arc_repository);
+        let mempool = init_mempool(NonceGapPolicy::Reject);
+        let route = routes(repository.clone(), Arc::clone(&mempool));
+
+        mempool.submit(signed(1, 0, "a"), 0).unwrap();
+        mempool.submit(signed(2, 0, "b"), 0).unwrap();
+        let block = produce_block(&mempool, &repository).await.unwrap().unwrap();
+
+        let get = |path: &str| warp::test::request().method("GET").path(path);
+        let body = |response: warp::http::Response<warp::hyper::body::Bytes>| -> serde_json::Value {
+            assert_eq!(response.status(), 200);
+            serde_json::from_slice(response.body()).unwrap()
+        };
+        let proof = |value: &serde_json::Value| -> StateProof {
+            serde_json::from_value(value["proof"].clone()).unwrap()
+        };
+
+        let plain = body(get("/transaction/get/1").reply(&route).await);
+        let proven = body(get("/transaction/get/1?prove=true").reply(&route).await);
+        let nonce = body(get(&format!("/account/{}/nonce?prove=true", signed(1, 0, "").sender)).reply(&route).await);
+        let unused = body(get(&format!("/account/{}/nonce?prove=true", signed(3, 0, "").sender)).reply(&route).await);
+        let listed = body(get("/transaction/list?prove=true").reply(&route).await);
+        let block_proof = get("/block/latest?prove=true").reply(&route).await;
+        let mempool_proof = get("/mempool?prove=true").reply(&route).await;
+        let missing = body(get("/transaction/get/9?prove=true").reply(&route).await);
+        let missing_plain = get("/transaction/get/9").reply(&route).await;
+        arc_repository.delete_transaction(&2, 1, &signed(2, 1, "delete")).unwrap();
+        let latest = arc_repository.find_block(&BlockId::Latest).unwrap();
+        let deleted = body(get("/transaction/get/2?prove=true").reply(&route).await);
+        let block_of = |value: &serde_json::Value| -> ProofBlock {
+            serde_json::from_value(value["block"].clone()).unwrap()
+        };
+
+        assert!(plain.get("proof").is_none());
+        assert!(plain.get("block").is_none());
+        assert_eq!(block_of(&proven), ProofBlock { height: block.height, hash: block.hash });
+        assert_eq!(block_of(&nonce), ProofBlock { height: block.height, hash: block.hash });
+        assert_eq!(block_of(&listed), ProofBlock { height: block.height, hash: block.hash });
+        assert_eq!(proven["payload"], plain["payload"]);
+        let stored = StoredRecord::decode(&hex::decode(proof(&proven).value.unwrap()).unwrap()).unwrap();
+        assert_eq!(stored, StoredRecord::Live { version: 1, transaction: signed(1, 0, "a") });
+        assert_eq!(proof(&proven).root, block.state_root);
+        assert_eq!(verify_proof(&block.state_root, &proof(&proven)), Ok(()));
+        assert_eq!(nonce["committed_nonce"], 1);
+        assert_eq!(verify_proof(&block.state_root, &proof(&nonce)), Ok(()));
+        assert_eq!(unused["committed_nonce"], 0);
+        assert_eq!(proof(&unused).value, None);
+        assert_eq!(verify_proof(&block.state_root, &proof(&unused)), Ok(()));
+        for item in listed["transactions"].as_array().unwrap() {
+            assert_eq!(verify_proof(&block.state_root, &proof(item)), Ok(()));
+        }
+        assert_eq!(listed["transactions"].as_array().unwrap().len(), 2);
+        assert_eq!(block_proof.status(), 400);
+        assert_eq!(mempool_proof.status(), 400);
+        assert!(missing.get("payload").is_none());
+        assert_eq!(proof(&missing).value, None);
+        assert_eq!(verify_proof(&block.state_root, &proof(&missing)), Ok(()));
+        assert_eq!(missing_plain.status(), 404);
+        assert_eq!(block_of(&deleted), ProofBlock { height: latest.height, hash: latest.hash });
+        assert!(deleted.get("payload").is_none());
+        assert!(matches!(
+            StoredRecord::decode(&hex::decode(proof(&deleted).value.unwrap()).unwrap()),
+            Ok(StoredRecord::Tombstone { .. })
+        ));
+        assert_eq!(verify_proof(&latest.state_root, &proof(&deleted)), Ok(()));
+    }
+
+
+    #[tokio::test]
+    async fn test_get_mempool_lists_pending_by_fee() {
+        let arc_repository = init_repository();
+        let route = routes(async_repository(&arc_repository), init_mempool(NonceGapPolicy::Reject));
+
+        for body in [with_fee(1, 0, 1, "cheap"), with_fee(2, 0, 5, "dear")] {
+            let response = warp::test::request()
+                .method("POST")
+                .path("/transaction/post")
+                .json(&body)
+                .reply(&route)
+                .await;
+            assert_eq!(response.status(), 202);
+        }
+        let response = warp::test::request()
+            .method("GET")
+            .path("/mempool")
+            .reply(&route)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["count"], 2);
+        assert_eq!(body["capacity"], 100);
+        assert_eq!(body["transactions"][0]["payload"], "dear");
+        assert_eq!(body["transactions"][0]["sender"], signed(2, 0, "").sender);
+        assert_eq!(body["transactions"][1]["fee"], 1);
+        assert!(body["transactions"][1]["age_ms"].is_u64());
     }
 }
@@ -0,0 +1,376 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+
+use std::net::{ IpAddr, Ipv4Addr, SocketAddr };
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
+
+use crate::db::store::Durability;
+use crate::ledger::Hash;
+use crate::mempool::{MempoolConfig, NonceGapPolicy};
+
+pub const DEFAULT_BIND_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+pub const DEFAULT_PORT: u16 = 3690;
+pub const DEFAULT_DB_PATH: &str = "./grid_db";
+pub const DEFAULT_GROUP_COMMIT_MS: u64 = 5;
+pub const DEFAULT_STORAGE_CONCURRENCY: usize = 32;
+pub const DEFAULT_STORAGE_QUEUE_MS: u64 = 1000;
+pub const DEFAULT_MAX_NONCE_GAP: u64 = 16;
+pub const DEFAULT_MEMPOOL_CAPACITY: usize = 10_000;
+pub const DEFAULT_MEMPOOL_SENDER_LIMIT: usize = 64;
+pub const DEFAULT_MEMPOOL_TTL_SECS: u64 = 600;
+pub const DEFAULT_BLOCK_INTERVAL_MS: u64 = 1000;
+
+/// Which `KvStore` backend holds the node's records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// LevelDB at `db_path`.
+    #[default]
+    Leveldb,
+    /// In-memory store; everything is lost on exit.
+    Memory,
+}
+
+/// When LevelDB writes are acknowledged. Ignored by the memory backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DurabilityMode {
+    /// fsync every write before acknowledging it.
+    #[default]
+    Sync,
+    /// Share one fsync between the writes of each group commit interval.
+    GroupCommit,
+    /// Acknowledge once the OS has the write; survives the process being
+    /// killed, not an OS crash or power loss.
+    Async,
+}
+
+/// What happens to a transaction whose nonce skips ahead of its sender's.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NonceGapMode {
+    /// Refuse it with 409.
+    #[default]
+    Reject,
+    /// Hold it, up to max_nonce_gap ahead, until the gap is filled.
+    Hold,
+}
+
+/// Command line flags. Each flag can also be set through its environment
+/// variable; anything left unset falls back to the config file, then to the defaults.
+#[derive(Debug, Default, Parser)]
+#[command(name = "grid_state_machine", version, about = "zGRID state machine node")]
+pub struct CliArgs {
+    /// TOML config file.
+    #[arg(long, env = "GRID_CONFIG", value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Address the HTTP server binds to [default: 127.0.0.1].
+    #[arg(long, env = "GRID_BIND_ADDRESS", value_name = "IP")]
+    pub bind_address: Option<IpAddr>,
+
+    /// Port the HTTP server listens on [default: 3690].
+    #[arg(long, env = "GRID_PORT", value_name = "PORT")]
+    pub port: Option<u16>,
+
+    /// LevelDB directory [default: ./grid_db].
+    #[arg(long, env = "GRID_DB_PATH", value_name = "PATH")]
+    pub db_path: Option<String>,
+
+    /// Storage backend [default: leveldb].
+    #[arg(long, env = "GRID_STORAGE", value_enum)]
+    pub storage: Option<StorageBackend>,
+
+    /// Write durability for the LevelDB backend [default: sync].
+    #[arg(long, env = "GRID_DURABILITY", value_enum)]
+    pub durability: Option<DurabilityMode>,
+
+    /// Group commit interval in milliseconds [default: 5].
+    #[arg(long, env = "GRID_GROUP_COMMIT_MS", value_name = "MS")]
+    pub group_commit_ms: Option<u64>,
+
+    /// Storage calls the API runs at once [default: 32].
+    #[arg(long, env = "GRID_STORAGE_CONCURRENCY", value_name = "N")]
+    pub storage_concurrency: Option<usize>,
+
+    /// How long a request waits for a storage slot before a 503 [default: 1000].
+    #[arg(long, env = "GRID_STORAGE_QUEUE_MS", value_name = "MS")]
+    pub storage_queue_ms: Option<u64>,
+
+    /// Policy for transactions whose nonce skips ahead [default: reject].
+    #[arg(long, env = "GRID_NONCE_GAP", value_enum)]
+    pub nonce_gap: Option<NonceGapMode>,
+
+    /// How far ahead a held nonce may be [default: 16].
+    #[arg(long, env = "GRID_MAX_NONCE_GAP", value_name = "N")]
+    pub max_nonce_gap: Option<u64>,
+
+    /// Pending transactions the mempool holds [default: 10000].
+    #[arg(long, env = "GRID_MEMPOOL_CAPACITY", value_name = "N")]
+    pub mempool_capacity: Option<usize>,
+
+    /// Pending transactions one sender may have [default: 64].
+    #[arg(long, env = "GRID_MEMPOOL_SENDER_LIMIT", value_name = "N")]
+    pub mempool_sender_limit: Option<usize>,
+
+    /// Seconds a transaction may stay pending before it is dropped [default: 600].
+    #[arg(long, env = "GRID_MEMPOOL_TTL_SECS", value_name = "SECS")]
+    pub mempool_ttl_secs: Option<u64>,
+
+    /// How often a block is produced from the mempool [default: 1000].
+    #[arg(long, env = "GRID_BLOCK_INTERVAL_MS", value_name = "MS")]
+    pub block_interval_ms: Option<u64>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Maintenance commands. Without one the node serves the HTTP API.
+#[derive(Debug, Clone, PartialEq, Subcommand)]
+pub enum Command {
+    /// List stored records that do not decode as transactions.
+    Scan {
+        /// Delete the corrupt records after listing them.
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Print a transaction signed with an ed25519 key file, such as a
+    /// grid_gossip `grid_node.key`, ready to POST.
+    Sign {
+        /// Protobuf-encoded ed25519 keypair.
+        #[arg(long, value_name = "PATH")]
+        key: PathBuf,
+        #[arg(long)]
+        nonce: u64,
+        #[arg(long, default_value_t = 0)]
+        fee: u64,
+        #[arg(long)]
+        payload: String,
+    },
+    /// Check a state proof, as returned by a read with `?prove=true`,
+    /// against a trusted state root such as a block's `state_root`.
+    Verify {
+        /// The saved reply, or just its `proof` object.
+        #[arg(long, value_name = "PATH")]
+        proof: PathBuf,
+        #[arg(long, value_name = "HEX")]
+        root: Hash,
+    },
+}
+
+/// Settings read from the TOML config file. Every key is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub bind_address: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub db_path: Option<String>,
+    pub storage: Option<StorageBackend>,
+    pub durability: Option<DurabilityMode>,
+    pub group_commit_ms: Option<u64>,
+    pub storage_concurrency: Option<usize>,
+    pub storage_queue_ms: Option<u64>,
+    pub nonce_gap: Option<NonceGapMode>,
+    pub max_nonce_gap: Option<u64>,
+    pub mempool_capacity: Option<usize>,
+    pub mempool_sender_limit: Option<usize>,
+    pub mempool_ttl_secs: Option<u64>,
+    pub block_interval_ms: Option<u64>,
+}
+
+impl FileConfig {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Config: Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&contents)
+            .map_err(|e| format!("Config: Invalid config file {}: {}", path.display(), e))
+    }
+}
+
+/// Resolved settings for one state machine node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub bind_address: IpAddr,
+    pub port: u16,
+    pub db_path: String,
+    pub storage: StorageBackend,
+    pub durability: DurabilityMode,
+    pub group_commit_ms: u64,
+    pub storage_concurrency: usize,
+    pub storage_queue_ms: u64,
+    pub nonce_gap: NonceGapMode,
+    pub max_nonce_gap: u64,
+    pub mempool_capacity: usize,
+    pub mempool_sender_limit: usize,
+    pub mempool_ttl_secs: u64,
+    pub block_interval_ms: u64,
+}
+
+impl Config {
+    /// Resolves the config from the process arguments and environment,
+    /// along with the maintenance command to run, if any.
+    pub fn load() -> Result<(Self, Option<Command>), String> {
+        let mut args = CliArgs::parse();
+        let command = args.command.take();
+        Ok((Config::from_args(args)?, command))
+    }
+
+
+    pub fn from_args(args: CliArgs) -> Result<Self, String> {
+        let file = match &args.config {
+            Some(path) => FileConfig::read(path)?,
+            None => FileConfig::default(),
+        };
+        let config = Config::merge(args, file);
+        if config.storage_concurrency == 0 {
+            return Err("Config: storage_concurrency must be at least 1".to_string());
+        }
+        if config.mempool_capacity == 0 || config.mempool_sender_limit == 0 {
+            return Err("Config: mempool_capacity and mempool_sender_limit must be at least 1".to_string());
+        }
+        if config.block_interval_ms == 0 {
+            return Err("Config: block_interval_ms must be at least 1".to_string());
+        }
+        Ok(config)
+    }
+
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+
+    pub fn storage_queue_timeout(&self) -> Duration {
+        Duration::from_millis(self.storage_queue_ms)
+    }
+
+
+    /// Durability guarantee the LevelDB backend is opened with.
+    pub fn durability(&self) -> Durability {
+        match self.durability {
+            DurabilityMode::Sync => Durability::Sync,
+            DurabilityMode::GroupCommit => Durability::GroupCommit(Duration::from_millis(self.group_commit_ms)),
+            DurabilityMode::Async => Durability::Async,
+        }
+    }
+
+
+    pub fn block_interval(&self) -> Duration {
+        Duration::from_millis(self.block_interval_ms)
+    }
+
+
+    pub fn nonce_gap_policy(&self) -> NonceGapPolicy {
+        match self.nonce_gap {
+            NonceGapMode::Reject => NonceGapPolicy::Reject,
+            NonceGapMode::Hold => NonceGapPolicy::Hold { max_gap: self.max_nonce_gap },
+        }
+    }
+
+
+    pub fn mempool_config(&self) -> MempoolConfig {
+        MempoolConfig {
+            capacity: self.mempool_capacity,
+            per_sender: self.mempool_sender_limit,
+            ttl: Duration::from_secs(self.mempool_ttl_secs),
+            nonce_gap: self.nonce_gap_policy(),
+        }
+    }
+
+
+    fn merge(args: CliArgs, file: FileConfig) -> Self {
+        Config {
+            bind_address: args.bind_address.or(file.bind_address).unwrap_or(DEFAULT_BIND_ADDRESS),
+            port: args.port.or(file.port).unwrap_or(DEFAULT_PORT),
+            db_path: args.db_path.or(file.db_path).unwrap_or_else(|| DEFAULT_DB_PATH.to_string()),
+            storage: args.storage.or(file.storage).unwrap_or_default(),
+            durability: args.durability.or(file.durability).unwrap_or_default(),
+            group_commit_ms: args.group_commit_ms.or(file.group_commit_ms).unwrap_or(DEFAULT_GROUP_COMMIT_MS),
+            storage_concurrency: args.storage_concurrency.or(file.storage_concurrency).unwrap_or(DEFAULT_STORAGE_CONCURRENCY),
+            storage_queue_ms: args.storage_queue_ms.or(file.storage_queue_ms).unwrap_or(DEFAULT_STORAGE_QUEUE_MS),
+            nonce_gap: args.nonce_gap.or(file.nonce_gap).unwrap_or_default(),
+            max_nonce_gap: args.max_nonce_gap.or(file.max_nonce_gap).unwrap_or(DEFAULT_MAX_NONCE_GAP),
+            mempool_capacity: args.mempool_capacity.or(file.mempool_capacity).unwrap_or(DEFAULT_MEMPOOL_CAPACITY),
+            mempool_sender_limit: args.mempool_sender_limit.or(file.mempool_sender_limit).unwrap_or(DEFAULT_MEMPOOL_SENDER_LIMIT),
+            mempool_ttl_secs: args.mempool_ttl_secs.or(file.mempool_ttl_secs).unwrap_or(DEFAULT_MEMPOOL_TTL_SECS),
+            block_interval_ms: args.block_interval_ms.or(file.block_interval_ms).unwrap_or(DEFAULT_BLOCK_INTERVAL_MS),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ CliArgs, Config, DurabilityMode, StorageBackend, DEFAULT_BIND_ADDRESS, DEFAULT_DB_PATH, DEFAULT_PORT };
+    use crate::db::store::Durability;
+    use crate::mempool::{MempoolConfig, NonceGapPolicy};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn write_config_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("grid_state_machine_{}_{}.toml", name, std::process::id()));
+        std::fs::write(&path, contents).expect("Failed to write config file.");
+        path
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::from_args(CliArgs::default()).unwrap();
+
+        assert_eq!(config.bind_address, DEFAULT_BIND_ADDRESS);
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.db_path, DEFAULT_DB_PATH);
+        assert_eq!(config.storage, StorageBackend::Leveldb);
+        assert_eq!(config.durability(), Durability::Sync);
+        assert_eq!(config.nonce_gap_policy(), NonceGapPolicy::Reject);
+        assert_eq!(config.block_interval(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_flags_override_config_file() {
+        let path = write_config_file("precedence", "bind_address = \"0.0.0.0\"\nport = 3699\ndb_path = \"./file_db\"\nstorage = \"memory\"\ndurability = \"group-commit\"\ngroup_commit_ms = 20\nnonce_gap = \"hold\"\nmax_nonce_gap = 4\nmempool_capacity = 500\nmempool_ttl_secs = 30\n");
+
+        let args = CliArgs {
+            config: Some(path.clone()),
+            port: Some(4000),
+            group_commit_ms: Some(10),
+            mempool_sender_limit: Some(8),
+            ..CliArgs::default()
+        };
+        let config = Config::from_args(args);
+
+        std::fs::remove_file(&path)
+            .expect("Failed to remove config file.");
+
+        let config = config.unwrap();
+        assert_eq!(config.bind_address.to_string(), "0.0.0.0");
+        assert_eq!(config.port, 4000);
+        assert_eq!(config.db_path, "./file_db");
+        assert_eq!(config.storage, StorageBackend::Memory);
+        assert_eq!(config.durability, DurabilityMode::GroupCommit);
+        assert_eq!(config.durability(), Durability::GroupCommit(Duration::from_millis(10)));
+        assert_eq!(config.mempool_config(), MempoolConfig {
+            capacity: 500,
+            per_sender: 8,
+            ttl: Duration::from_secs(30),
+            nonce_gap: NonceGapPolicy::Hold { max_gap: 4 },
+        });
+    }
+
+    #[test]
+    fn test_unknown_config_keys_are_refused() {
+        let path = write_config_file("unknown", "prot = 3699\n");
+
+        let config = Config::from_args(CliArgs { config: Some(path.clone()), ..CliArgs::default() });
+        let no_storage_slots = Config::from_args(CliArgs { storage_concurrency: Some(0), ..CliArgs::default() });
+        let empty_mempool = Config::from_args(CliArgs { mempool_capacity: Some(0), ..CliArgs::default() });
+
+        std::fs::remove_file(&path)
+            .expect("Failed to remove config file.");
+
+        assert!(config.is_err());
+        assert!(no_storage_slots.is_err());
+        assert!(empty_mempool.is_err());
+    }
+}
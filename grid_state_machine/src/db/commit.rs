@@ -0,0 +1,160 @@
+use leveldb::database::Database as GRID_DB;
+use leveldb::kv::KV;
+use leveldb::options::WriteOptions;
+use log::error;
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::db::DbError;
+use crate::db::keys::{DbKey, Keyspace};
+
+#[derive(Debug, Default)]
+struct CommitState {
+    /// Writes acknowledged by LevelDB but possibly still in the OS cache.
+    written: u64,
+    /// Highest write known to be on disk.
+    synced: u64,
+    shutdown: bool,
+    /// Set once the flusher has made its last sync; writes it did not cover
+    /// by then never will be.
+    stopped: bool,
+}
+
+/// Group commit for `Durability::GroupCommit`: writers do a non-synced
+/// write, then block until a background thread's next synced write, which
+/// flushes the LevelDB log, covers theirs. One fsync per interval serves
+/// every writer that arrived during it.
+pub struct GroupCommitter {
+    state: Arc<(Mutex<CommitState>, Condvar)>,
+    flusher: Option<JoinHandle<()>>,
+}
+
+impl GroupCommitter {
+    pub fn start(database: Arc<GRID_DB<DbKey>>, interval: Duration) -> Self {
+        let state = Arc::new((Mutex::new(CommitState::default()), Condvar::new()));
+        let flusher_state = Arc::clone(&state);
+        let flusher = std::thread::Builder::new()
+            .name("grid-group-commit".to_string())
+            .spawn(move || run_flusher(database, flusher_state, interval))
+            .expect("DB: Failed to spawn group commit thread");
+
+        GroupCommitter { state, flusher: Some(flusher) }
+    }
+
+
+    /// Call after a non-synced write returns; blocks until it is on disk.
+    /// Fails if the committer stops before a sync covers the write.
+    pub fn wait_for_sync(&self) -> Result<(), DbError> {
+        let (lock, synced) = &*self.state;
+        let mut state = lock_state(lock);
+        state.written += 1;
+        let ticket = state.written;
+
+        while state.synced < ticket {
+            if state.stopped {
+                return Err(DbError::Sync { message: "group commit stopped before the write was synced".to_string() });
+            }
+            state = synced.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        Ok(())
+    }
+}
+
+/// Stops the flusher after a final sync of every write made so far.
+impl Drop for GroupCommitter {
+    fn drop(&mut self) {
+        let (lock, synced) = &*self.state;
+        lock_state(lock).shutdown = true;
+        synced.notify_all();
+
+        if let Some(flusher) = self.flusher.take() {
+            let _ = flusher.join();
+        }
+        // Covers a flusher that panicked before marking itself stopped.
+        lock_state(lock).stopped = true;
+        synced.notify_all();
+    }
+}
+
+
+fn run_flusher(database: Arc<GRID_DB<DbKey>>, state: Arc<(Mutex<CommitState>, Condvar)>, interval: Duration) {
+    let (lock, synced) = &*state;
+    let marker = DbKey::new(Keyspace::Meta).push("group_commit");
+
+    loop {
+        let mut guard = lock_state(lock);
+        if !guard.shutdown {
+            guard = synced.wait_timeout(guard, interval)
+                .unwrap_or_else(|poisoned| poisoned.into_inner()).0;
+        }
+        let target = guard.written;
+        let shutdown = guard.shutdown;
+
+        if target > guard.synced {
+            drop(guard);
+            let mut options = WriteOptions::new();
+            options.sync = true;
+            match database.put(options, &marker, &target.to_be_bytes()) {
+                Ok(()) => {
+                    guard = lock_state(lock);
+                    guard.synced = guard.synced.max(target);
+                    synced.notify_all();
+                }
+                Err(e) => {
+                    error!("DB: Group commit sync failed: {}", e);
+                    guard = lock_state(lock);
+                }
+            }
+        }
+
+        // Writes that raced a shutdown seen during the sync get another
+        // round; once shutdown was seen before it, this was the final sync.
+        if shutdown {
+            guard.stopped = true;
+            synced.notify_all();
+            return;
+        }
+    }
+}
+
+
+fn lock_state(lock: &Mutex<CommitState>) -> MutexGuard<'_, CommitState> {
+    lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{DatabaseState, DbError};
+    use crate::db::commit::{lock_state, GroupCommitter};
+    use crate::db::store::Durability;
+    use crate::db::testing::DirGuard;
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_shutdown_syncs_before_releasing_waiters() {
+        let _guard = DirGuard("./test_db_group_commit_shutdown".to_string());
+        let db_state = DatabaseState::open("./test_db_group_commit_shutdown".to_string(), Durability::Async).unwrap();
+        let committer = GroupCommitter::start(Arc::clone(&db_state.database), Duration::from_secs(600));
+        let (lock, synced) = &*committer.state;
+
+        let waited = std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| committer.wait_for_sync());
+            while lock_state(lock).written == 0 {
+                std::thread::yield_now();
+            }
+            lock_state(lock).shutdown = true;
+            synced.notify_all();
+            waiter.join().unwrap()
+        });
+        let late = committer.wait_for_sync();
+
+        assert_eq!(waited, Ok(()));
+        assert_eq!(lock_state(lock).synced, 1);
+        assert!(matches!(late, Err(DbError::Sync { .. })));
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+
+/// Column-like keyspaces sharing the one LevelDB. Every key starts with
+/// its keyspace prefix, so each keyspace iterates as one contiguous range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyspace {
+    Meta,
+    Transaction,
+    Block,
+    Job,
+    Account,
+}
+
+impl Keyspace {
+    pub const ALL: [Keyspace; 5] = [
+        Keyspace::Meta,
+        Keyspace::Transaction,
+        Keyspace::Block,
+        Keyspace::Job,
+        Keyspace::Account,
+    ];
+
+    pub fn prefix(&self) -> &'static [u8] {
+        match self {
+            Keyspace::Meta => b"meta/",
+            Keyspace::Transaction => b"tx/",
+            Keyspace::Block => b"blk/",
+            Keyspace::Job => b"job/",
+            Keyspace::Account => b"acct/",
+        }
+    }
+}
+
+/// One component of a composite key. Encodings preserve ordering, so keys
+/// built from the same parts sort the way the values do.
+pub trait KeyPart {
+    fn encode_into(&self, out: &mut Vec<u8>);
+}
+
+impl KeyPart for u32 {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl KeyPart for u64 {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+/// Big-endian with the sign bit flipped, so negative values sort first.
+impl KeyPart for i32 {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&((*self as u32) ^ 0x8000_0000).to_be_bytes());
+    }
+}
+
+/// Fixed-width digests such as 32-byte hashes and public keys.
+impl<const N: usize> KeyPart for [u8; N] {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+/// Raw bytes are appended as-is, so only use them as the last part.
+impl KeyPart for &[u8] {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl KeyPart for &str {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+/// Byte key for `KvStore` backends. The default key is empty and sorts first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DbKey(Vec<u8>);
+
+impl DbKey {
+    pub fn new(keyspace: Keyspace) -> Self {
+        DbKey(keyspace.prefix().to_vec())
+    }
+
+
+    /// The first key past every key of `keyspace`; it belongs to no keyspace.
+    pub fn keyspace_end(keyspace: Keyspace) -> Self {
+        let mut bytes = keyspace.prefix().to_vec();
+        if let Some(last) = bytes.last_mut() {
+            *last += 1;
+        }
+        DbKey(bytes)
+    }
+
+
+    pub fn push(mut self, part: impl KeyPart) -> Self {
+        part.encode_into(&mut self.0);
+        self
+    }
+
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+
+    pub fn keyspace(&self) -> Option<Keyspace> {
+        Keyspace::ALL.into_iter().find(|keyspace| self.0.starts_with(keyspace.prefix()))
+    }
+
+
+    /// The part of the key after its keyspace prefix.
+    pub fn suffix(&self, keyspace: Keyspace) -> Option<&[u8]> {
+        self.0.strip_prefix(keyspace.prefix())
+    }
+}
+
+impl fmt::Display for DbKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (prefix, rest) = match self.keyspace() {
+            Some(keyspace) => (keyspace.prefix(), &self.0[keyspace.prefix().len()..]),
+            None => (&[][..], &self.0[..]),
+        };
+        write!(f, "{}", String::from_utf8_lossy(prefix))?;
+        for byte in rest {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl db_key::Key for DbKey {
+    fn from_u8(key: &[u8]) -> Self {
+        DbKey(key.to_vec())
+    }
+
+    fn as_slice<T, F: Fn(&[u8]) -> T>(&self, f: F) -> T {
+        f(&self.0)
+    }
+}
+
+
+/// Decodes an i32 written by its `KeyPart` impl.
+pub fn decode_i32(bytes: &[u8]) -> Option<i32> {
+    let bytes: [u8; 4] = bytes.try_into().ok()?;
+    Some((u32::from_be_bytes(bytes) ^ 0x8000_0000) as i32)
+}
+
+
+/// Decodes a u64 written by its `KeyPart` impl.
+pub fn decode_u64(bytes: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.try_into().ok()?))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::db::keys::{decode_i32, DbKey, Keyspace};
+    use db_key::Key;
+
+    #[test]
+    fn test_i32_keys_sort_numerically() {
+        let values = [i32::MIN, -300, -1, 0, 1, 2, 256, i32::MAX];
+        let keys: Vec<DbKey> = values.iter()
+            .map(|value| DbKey::new(Keyspace::Transaction).push(*value))
+            .collect();
+
+        let mut sorted = keys.clone();
+        sorted.sort();
+
+        assert_eq!(sorted, keys);
+        for (key, value) in keys.iter().zip(values) {
+            assert_eq!(decode_i32(key.suffix(Keyspace::Transaction).unwrap()), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_composite_keys() {
+        let hash = [0xabu8; 32];
+        let key = DbKey::new(Keyspace::Account).push(hash).push(7u64);
+
+        assert_eq!(key.keyspace(), Some(Keyspace::Account));
+        assert_eq!(key.as_bytes().len(), "acct/".len() + 32 + 8);
+        assert_eq!(&key.suffix(Keyspace::Account).unwrap()[32..], &7u64.to_be_bytes());
+        assert_eq!(DbKey::new(Keyspace::Meta).push("tx_counter").to_string(), "meta/74785f636f756e746572");
+        assert_eq!(DbKey::from_u8(&[0, 0, 0, 1]).keyspace(), None);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::db::DbError;
+use crate::db::keys::DbKey;
+use crate::db::store::{BatchOp, Durability, KvIter, KvSnapshot, KvStore, WriteBatch};
+
+use std::collections::BTreeMap;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// In-memory `KvStore` backed by a `BTreeMap`. Nothing is persisted, which
+/// makes it the backend for tests and for embedders that keep state elsewhere.
+/// Iterators and snapshots copy the data they cover, so they stay consistent
+/// while the store keeps changing.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    data: RwLock<BTreeMap<DbKey, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+
+    fn read_guard(&self) -> RwLockReadGuard<'_, BTreeMap<DbKey, Vec<u8>>> {
+        self.data.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+
+    fn write_guard(&self) -> RwLockWriteGuard<'_, BTreeMap<DbKey, Vec<u8>>> {
+        self.data.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl KvStore for MemoryStore {
+    fn get(&self, key: &DbKey) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(self.read_guard().get(key).cloned())
+    }
+
+
+    #[cfg(test)]
+    fn put(&self, key: &DbKey, value: &[u8]) -> Result<(), DbError> {
+        self.write_guard().insert(key.clone(), value.to_vec());
+        Ok(())
+    }
+
+
+    fn delete(&self, key: &DbKey) -> Result<(), DbError> {
+        self.write_guard().remove(key);
+        Ok(())
+    }
+
+
+    fn write_batch(&self, batch: &WriteBatch) -> Result<(), DbError> {
+        let mut data = self.write_guard();
+        for op in batch.ops() {
+            match op {
+                BatchOp::Put(key, value) => {
+                    data.insert(key.clone(), value.clone());
+                }
+                BatchOp::Delete(key) => {
+                    data.remove(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+
+    fn iter_from(&self, start: &DbKey) -> KvIter<'_> {
+        let entries: Vec<(DbKey, Vec<u8>)> = self.read_guard()
+            .range(start.clone()..)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Box::new(entries.into_iter())
+    }
+
+
+    fn iter_rev_from(&self, start: &DbKey) -> KvIter<'_> {
+        let entries: Vec<(DbKey, Vec<u8>)> = self.read_guard()
+            .range(..=start.clone())
+            .rev()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Box::new(entries.into_iter())
+    }
+
+
+    fn snapshot(&self) -> Box<dyn KvSnapshot + '_> {
+        Box::new(MemorySnapshot { data: self.read_guard().clone() })
+    }
+
+
+    fn durability(&self) -> Durability {
+        Durability::Volatile
+    }
+}
+
+struct MemorySnapshot {
+    data: BTreeMap<DbKey, Vec<u8>>,
+}
+
+impl KvSnapshot for MemorySnapshot {
+    fn get(&self, key: &DbKey) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(self.data.get(key).cloned())
+    }
+
+
+    fn iter_from(&self, start: &DbKey) -> KvIter<'_> {
+        Box::new(self.data.range(start.clone()..)
+            .map(|(key, value)| (key.clone(), value.clone())))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::db::memory::MemoryStore;
+    use crate::db::store::conformance::check_store;
+
+    #[test]
+    fn test_memory_store_conformance() {
+        check_store(&MemoryStore::new());
+    }
+}
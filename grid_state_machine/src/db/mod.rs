@@ -1,92 +1,381 @@
+mod commit;
+pub mod keys;
+pub mod memory;
+pub mod store;
+
+use leveldb::batch::{Batch, Writebatch};
 use leveldb::database::Database as GRID_DB;
+use leveldb::iterator::{Iterable, LevelDBIterator};
 use leveldb::kv::KV;
 use leveldb::options::{Options, WriteOptions, ReadOptions};
+use leveldb::snapshots::{Snapshot, Snapshots};
 
-use std::error::Error;
+use std::fmt;
 use std::path::Path;
+use std::sync::Arc;
+
+use commit::GroupCommitter;
+use keys::DbKey;
+use store::{BatchOp, Durability, KvIter, KvSnapshot, KvStore, WriteBatch};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbError {
+    Open { path: String, message: String },
+    Read { key: DbKey, message: String },
+    #[cfg(test)]
+    Write { key: DbKey, message: String },
+    Delete { key: DbKey, message: String },
+    Batch { message: String },
+    /// A group-committed write was acknowledged by LevelDB but never synced.
+    Sync { message: String },
+    NotFound(DbKey),
+}
 
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Open { path, message } => write!(f, "DB: Failed to open {}: {}", path, message),
+            DbError::Read { key, message } => write!(f, "DB: Failed to read key {}: {}", key, message),
+            #[cfg(test)]
+            DbError::Write { key, message } => write!(f, "DB: Failed to write key {}: {}", key, message),
+            DbError::Delete { key, message } => write!(f, "DB: Failed to delete key {}: {}", key, message),
+            DbError::Batch { message } => write!(f, "DB: Failed to write batch: {}", message),
+            DbError::Sync { message } => write!(f, "DB: Failed to sync: {}", message),
+            DbError::NotFound(key) => write!(f, "DB: Key {} not found", key),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+/// LevelDB-backed `KvStore`.
 pub struct DatabaseState {
     pub db_path_string: String,
-    pub database: GRID_DB<i32>,
+    pub database: Arc<GRID_DB<DbKey>>,
+    durability: Durability,
+    group_commit: Option<GroupCommitter>,
 }
 
 impl DatabaseState {
-    pub fn init(db_path_string: String) -> Self { 
+    /// Opens the LevelDB directory, creating it if missing. Writes are
+    /// acknowledged according to `durability`.
+    pub fn open(db_path_string: String, durability: Durability) -> Result<Self, DbError> {
         let path = Path::new(&db_path_string);
 
-        let mut options = Options::new();
-        options.create_if_missing = true;
-
-        let db = open_database(&path).unwrap();
+        let db = Arc::new(open_database(path)?);
+        let group_commit = match durability {
+            Durability::GroupCommit(interval) => Some(GroupCommitter::start(Arc::clone(&db), interval)),
+            _ => None,
+        };
         
-        Self {
+        Ok(Self {
             db_path_string: db_path_string.to_string(),
             database: db,
+            durability,
+            group_commit,
+        })
+    }
+
+
+    fn write_options(&self) -> WriteOptions {
+        let mut write_options = WriteOptions::new();
+        write_options.sync = self.durability == Durability::Sync;
+        write_options
+    }
+
+
+    /// Holds a write's acknowledgement until it meets the durability mode.
+    fn commit(&self) -> Result<(), DbError> {
+        match &self.group_commit {
+            Some(group_commit) => group_commit.wait_for_sync(),
+            None => Ok(()),
         }
     }
+}
+
+impl Drop for DatabaseState {
+    fn drop(&mut self) {
+        // Stop the flusher first so it releases its handle on the database.
+        self.group_commit.take();
+    }
+}
 
+impl KvStore for DatabaseState {
+    fn get(&self, key: &DbKey) -> Result<Option<Vec<u8>>, DbError> {
+        let read_options: ReadOptions<'static, DbKey> = ReadOptions::new();
+        self.database.get(read_options, key)
+            .map_err(|e| DbError::Read { key: key.clone(), message: e.to_string() })
+    }
 
-    pub fn insert_key(&self, key: &i32, value: &[u8]) -> Result<(), Box<dyn Error>> {
-        let write_options = WriteOptions::new();
-        self.database.put(write_options, key, value)?;
-        
-        Ok(())
+
+    #[cfg(test)]
+    fn put(&self, key: &DbKey, value: &[u8]) -> Result<(), DbError> {
+        self.database.put(self.write_options(), key, value)
+            .map_err(|e| DbError::Write { key: key.clone(), message: e.to_string() })?;
+        self.commit()
+    }
+
+
+    fn delete(&self, key: &DbKey) -> Result<(), DbError> {
+        self.database.delete(self.write_options(), key)
+            .map_err(|e| DbError::Delete { key: key.clone(), message: e.to_string() })?;
+        self.commit()
     }
 
 
-    pub fn read_key(&self, key: &i32) -> Result<Vec<u8>, Box<dyn Error>> {
-        let read_options: ReadOptions<'static, i32> = ReadOptions::new();
-        let data: Option<Vec<u8>> = self.database.get(read_options, key)?;
-        let result: Vec<u8> = data.ok_or("DB: Key not found")?;
+    fn write_batch(&self, batch: &WriteBatch) -> Result<(), DbError> {
+        let mut leveldb_batch: Writebatch<DbKey> = Writebatch::new();
+        for op in batch.ops() {
+            match op {
+                BatchOp::Put(key, value) => leveldb_batch.put(key.clone(), value),
+                BatchOp::Delete(key) => leveldb_batch.delete(key.clone()),
+            }
+        }
 
-        Ok(result)            
+        self.database.write(self.write_options(), &leveldb_batch)
+            .map_err(|e| DbError::Batch { message: e.to_string() })?;
+        self.commit()
     }
+
+
+    fn iter_from(&self, start: &DbKey) -> KvIter<'_> {
+        let iter = self.database.iter(ReadOptions::new());
+        iter.seek(start);
+
+        Box::new(iter)
+    }
+
+
+    fn iter_rev_from(&self, start: &DbKey) -> KvIter<'_> {
+        let iter = self.database.iter(ReadOptions::new()).reverse();
+        iter.seek(start);
+        if !iter.valid() {
+            iter.seek_to_last();
+        }
+        let start = start.clone();
+
+        Box::new(iter.skip_while(move |(key, _)| *key > start))
+    }
+
+
+    fn snapshot(&self) -> Box<dyn KvSnapshot + '_> {
+        Box::new(DatabaseSnapshot { snapshot: self.database.snapshot() })
+    }
+
+
+    fn durability(&self) -> Durability {
+        self.durability
+    }
+}
+
+struct DatabaseSnapshot<'a> {
+    snapshot: Snapshot<'a, DbKey>,
 }
 
+impl KvSnapshot for DatabaseSnapshot<'_> {
+    fn get(&self, key: &DbKey) -> Result<Option<Vec<u8>>, DbError> {
+        self.snapshot.get(ReadOptions::new(), key)
+            .map_err(|e| DbError::Read { key: key.clone(), message: e.to_string() })
+    }
+
+
+    fn iter_from(&self, start: &DbKey) -> KvIter<'_> {
+        let iter = self.snapshot.iter(ReadOptions::new());
+        iter.seek(start);
+
+        Box::new(iter)
+    }
+}
 
-fn open_database(path: &Path) -> Result<GRID_DB<i32>, Box<dyn Error>> { 
+
+fn open_database(path: &Path) -> Result<GRID_DB<DbKey>, DbError> { 
     let mut options = Options::new();
     options.create_if_missing = true;
 
-    let db = GRID_DB::open(path, options)?;
-    
-    Ok(db)
+    GRID_DB::open(path, options).map_err(|e| DbError::Open {
+        path: path.display().to_string(),
+        message: e.to_string(),
+    })
 }
 
+#[cfg(test)]
+pub mod testing {
+    /// Removes a test database directory on drop, even when the test
+    /// panics. Bind it before the `DatabaseState` so it drops last.
+    pub struct DirGuard(pub String);
+
+    impl Drop for DirGuard {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
-    use crate::db::{DatabaseState};
+    use crate::db::{DatabaseState, DbError};
+    use crate::db::keys::{DbKey, Keyspace};
+    use crate::db::store::{Durability, KvStore};
+    use crate::db::store::conformance::check_store;
+    use crate::db::testing::DirGuard;
+
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
 
-    fn init_database(db_path: String) -> (DatabaseState, String) {
-        let db_state = DatabaseState::init(db_path.clone());
-        (db_state, db_path)
+    fn init_database(db_path: &str) -> (DirGuard, DatabaseState) {
+        let guard = DirGuard(db_path.to_string());
+        let db_state = DatabaseState::open(db_path.to_string(), Durability::Sync).unwrap();
+        (guard, db_state)
     }
 
     #[test]
     fn test_open_database() {
-        let (db_state, db_path) = init_database("./test_db_open".to_string());
-        drop(db_state);
-        std::fs::remove_dir_all(db_path)
-            .expect("Failed to remove test db directory.");
+        let (_guard, db_state) = init_database("./test_db_open");
+
+        assert_eq!(db_state.db_path_string, "./test_db_open");
     }
 
     #[test]
     fn test_insert_and_read_key() {
-        let (db_state, db_path) = init_database("./test_db_insert_and_read_key"
-            .to_string());
+        let (_guard, db_state) = init_database("./test_db_insert_and_read_key");
  
-        let key = 369;
+        let key = DbKey::new(Keyspace::Transaction).push(369);
         let value = b"Hello, Meow!";
         let value_bytes = value.to_vec();
 
-        db_state.insert_key(&key, value).unwrap();
+        db_state.put(&key, value).unwrap();
 
-        let result = db_state.read_key(&key).unwrap();
-
-        drop(db_state);
-        std::fs::remove_dir_all(db_path)
-            .expect("Failed to remove test db directory.");
+        let result = db_state.read(&key).unwrap();
 
         assert_eq!(result, value_bytes);
     }
+
+
+    #[test]
+    fn test_missing_and_deleted_keys() {
+        let (_guard, db_state) = init_database("./test_db_missing_and_deleted_key");
+
+        let seven = DbKey::new(Keyspace::Transaction).push(7);
+        let eight = DbKey::new(Keyspace::Transaction).push(8);
+        db_state.put(&seven, b"seven").unwrap();
+        db_state.put(&eight, b"eight").unwrap();
+        db_state.delete(&seven).unwrap();
+
+        let deleted = db_state.read(&seven);
+        let entries: Vec<(DbKey, Vec<u8>)> = db_state.iter_from(&DbKey::default()).collect();
+
+        assert_eq!(deleted, Err(DbError::NotFound(seven)));
+        assert_eq!(entries, vec![(eight, b"eight".to_vec())]);
+    }
+
+
+    #[test]
+    fn test_keyspace_entries_stay_in_their_prefix() {
+        let (_guard, db_state) = init_database("./test_db_keyspace_entries");
+
+        for value in [3, -1, 2] {
+            db_state.put(&DbKey::new(Keyspace::Transaction).push(value), b"tx").unwrap();
+        }
+        db_state.put(&DbKey::new(Keyspace::Block).push(1u64), b"blk").unwrap();
+        db_state.put(&DbKey::new(Keyspace::Meta).push("counter"), b"meta").unwrap();
+
+        let transactions: Vec<DbKey> = db_state.keyspace_entries(Keyspace::Transaction)
+            .map(|(key, _)| key)
+            .collect();
+        let blocks = db_state.keyspace_entries(Keyspace::Block).count();
+        let jobs = db_state.keyspace_entries(Keyspace::Job).count();
+
+        let expected: Vec<DbKey> = [-1, 2, 3].iter()
+            .map(|value| DbKey::new(Keyspace::Transaction).push(*value))
+            .collect();
+        assert_eq!(transactions, expected);
+        assert_eq!(blocks, 1);
+        assert_eq!(jobs, 0);
+    }
+
+
+    #[test]
+    fn test_leveldb_store_conformance() {
+        let (_guard, db_state) = init_database("./test_db_conformance");
+
+        check_store(&db_state);
+    }
+
+
+    fn durability_from_name(name: &str) -> Durability {
+        match name {
+            "sync" => Durability::Sync,
+            "group-commit" => Durability::GroupCommit(Duration::from_millis(2)),
+            "async" => Durability::Async,
+            other => panic!("Unknown durability {}", other),
+        }
+    }
+
+    /// Child half of `test_acknowledged_writes_survive_process_kill`: writes keys
+    /// until killed, printing `ACK n` once each write returns. Does nothing
+    /// unless started by the parent test.
+    #[test]
+    #[ignore]
+    fn crash_child_writes_until_killed() {
+        let (Ok(db_path), Ok(mode)) = (std::env::var("GRID_CRASH_DB"), std::env::var("GRID_CRASH_DURABILITY")) else {
+            return;
+        };
+        let db_state = DatabaseState::open(db_path, durability_from_name(&mode)).unwrap();
+
+        for n in 0..100_000u32 {
+            db_state.put(&DbKey::new(Keyspace::Transaction).push(n), &n.to_be_bytes()).unwrap();
+            println!("ACK {}", n);
+        }
+    }
+
+    /// Kills a writer process mid-stream with SIGKILL and checks that every
+    /// write it acknowledged is readable after reopening the database. The OS
+    /// keeps running, so this covers a process kill only: `async` passes here
+    /// yet can still lose acknowledged writes on an OS crash or power loss.
+    #[test]
+    fn test_acknowledged_writes_survive_process_kill() {
+        for mode in ["sync", "group-commit", "async"] {
+            let db_path = format!("./test_db_crash_{}", mode);
+            let _guard = DirGuard(db_path.clone());
+
+            let mut child = Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "db::tests::crash_child_writes_until_killed", "--ignored", "--nocapture", "--test-threads=1"])
+                .env("GRID_CRASH_DB", &db_path)
+                .env("GRID_CRASH_DURABILITY", mode)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap();
+
+            let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+            let mut acknowledged = Vec::new();
+            while acknowledged.len() < 200 {
+                match lines.next() {
+                    Some(line) => acknowledged.extend(parse_ack(&line.unwrap())),
+                    None => break,
+                }
+            }
+            child.kill().unwrap();
+            child.wait().unwrap();
+            // Acknowledgements still in the pipe were printed before the kill.
+            acknowledged.extend(lines.map_while(Result::ok).filter_map(|line| parse_ack(&line)));
+
+            assert!(acknowledged.len() >= 200, "{}: child stopped after {} writes", mode, acknowledged.len());
+            // Only the process died, so the OS still flushes what it was handed.
+            let db_state = DatabaseState::open(db_path.clone(), Durability::Sync).unwrap();
+            for n in acknowledged {
+                let key = DbKey::new(Keyspace::Transaction).push(n);
+                assert_eq!(
+                    db_state.get(&key), Ok(Some(n.to_be_bytes().to_vec())),
+                    "{}: lost write {} after a process kill", mode, n
+                );
+            }
+        }
+    }
+
+    fn parse_ack(line: &str) -> Option<u32> {
+        line.strip_prefix("ACK ").and_then(|n| n.parse().ok())
+    }
 }
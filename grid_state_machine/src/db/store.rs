@@ -0,0 +1,240 @@
+use crate::db::DbError;
+use crate::db::keys::{DbKey, Keyspace};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Ordered key/value pairs, starting at a given key.
+pub type KvIter<'a> = Box<dyn Iterator<Item = (DbKey, Vec<u8>)> + 'a>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Ascending key order.
+    #[default]
+    Forward,
+    /// Descending key order.
+    Reverse,
+}
+
+/// When a write acknowledged by a `KvStore` is safe from power loss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Durability {
+    /// Every write is fsynced before it is acknowledged.
+    Sync,
+    /// Writes are acknowledged once a shared fsync, issued every interval,
+    /// covers them.
+    GroupCommit(Duration),
+    /// Writes are acknowledged once handed to the OS. They survive the
+    /// process being killed, but not an OS crash or power loss.
+    Async,
+    /// Nothing reaches disk; the in-memory backend.
+    Volatile,
+}
+
+impl Durability {
+    /// Name reported in API responses.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Durability::Sync => "sync",
+            Durability::GroupCommit(_) => "group-commit",
+            Durability::Async => "async",
+            Durability::Volatile => "volatile",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOp {
+    Put(DbKey, Vec<u8>),
+    Delete(DbKey),
+}
+
+/// Writes applied together by `KvStore::write_batch`: either every
+/// operation lands or none does.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        WriteBatch::default()
+    }
+
+
+    pub fn put(&mut self, key: DbKey, value: impl Into<Vec<u8>>) -> &mut Self {
+        self.ops.push(BatchOp::Put(key, value.into()));
+        self
+    }
+
+
+    pub fn delete(&mut self, key: DbKey) -> &mut Self {
+        self.ops.push(BatchOp::Delete(key));
+        self
+    }
+
+
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+}
+
+/// Read-only view of a store frozen at the time it was taken.
+pub trait KvSnapshot {
+    fn get(&self, key: &DbKey) -> Result<Option<Vec<u8>>, DbError>;
+
+    fn iter_from(&self, start: &DbKey) -> KvIter<'_>;
+}
+
+/// Storage backend behind `Repository`. Keys iterate in byte order.
+pub trait KvStore: Send + Sync {
+    fn get(&self, key: &DbKey) -> Result<Option<Vec<u8>>, DbError>;
+
+    /// Single-record write for tests; everything else writes a `WriteBatch`
+    /// so related records land together.
+    #[cfg(test)]
+    fn put(&self, key: &DbKey, value: &[u8]) -> Result<(), DbError>;
+
+    fn delete(&self, key: &DbKey) -> Result<(), DbError>;
+
+    fn write_batch(&self, batch: &WriteBatch) -> Result<(), DbError>;
+
+    fn iter_from(&self, start: &DbKey) -> KvIter<'_>;
+
+    /// Keys at or before `start`, in descending order.
+    fn iter_rev_from(&self, start: &DbKey) -> KvIter<'_>;
+
+    fn snapshot(&self) -> Box<dyn KvSnapshot + '_>;
+
+    /// Guarantee that applies to every acknowledged write.
+    fn durability(&self) -> Durability;
+
+
+    fn read(&self, key: &DbKey) -> Result<Vec<u8>, DbError> {
+        self.get(key)?.ok_or_else(|| DbError::NotFound(key.clone()))
+    }
+
+
+    /// The key/value pairs of one keyspace, in key order.
+    fn keyspace_entries(&self, keyspace: Keyspace) -> KvIter<'_> {
+        self.scan(keyspace, None, Direction::Forward)
+    }
+
+
+    /// Range scan over one keyspace, starting just past `after` (exclusive)
+    /// or at the keyspace's first key in `direction` when `after` is `None`.
+    fn scan(&self, keyspace: Keyspace, after: Option<&DbKey>, direction: Direction) -> KvIter<'_> {
+        let entries = match direction {
+            Direction::Forward => {
+                let start = after.cloned().unwrap_or_else(|| DbKey::new(keyspace));
+                self.iter_from(&start)
+            }
+            Direction::Reverse => {
+                let start = after.cloned().unwrap_or_else(|| DbKey::keyspace_end(keyspace));
+                self.iter_rev_from(&start)
+            }
+        };
+        let after = after.cloned();
+
+        Box::new(entries
+            .skip_while(move |(key, _)| Some(key) == after.as_ref())
+            .take_while(move |(key, _)| key.as_bytes().starts_with(keyspace.prefix())))
+    }
+}
+
+/// Lets several owners share one store, e.g. an embedder keeping a handle
+/// to the backend it passed to `Repository::new`.
+impl<S: KvStore + ?Sized> KvStore for Arc<S> {
+    fn get(&self, key: &DbKey) -> Result<Option<Vec<u8>>, DbError> {
+        (**self).get(key)
+    }
+
+    #[cfg(test)]
+    fn put(&self, key: &DbKey, value: &[u8]) -> Result<(), DbError> {
+        (**self).put(key, value)
+    }
+
+    fn delete(&self, key: &DbKey) -> Result<(), DbError> {
+        (**self).delete(key)
+    }
+
+    fn write_batch(&self, batch: &WriteBatch) -> Result<(), DbError> {
+        (**self).write_batch(batch)
+    }
+
+    fn iter_from(&self, start: &DbKey) -> KvIter<'_> {
+        (**self).iter_from(start)
+    }
+
+    fn iter_rev_from(&self, start: &DbKey) -> KvIter<'_> {
+        (**self).iter_rev_from(start)
+    }
+
+    fn snapshot(&self) -> Box<dyn KvSnapshot + '_> {
+        (**self).snapshot()
+    }
+
+    fn durability(&self) -> Durability {
+        (**self).durability()
+    }
+}
+
+
+/// Behaviour every backend must share; run from each backend's tests.
+#[cfg(test)]
+pub mod conformance {
+    use crate::db::DbError;
+    use crate::db::keys::{DbKey, Keyspace};
+    use crate::db::store::{Direction, KvStore, WriteBatch};
+
+    fn tx(key: i32) -> DbKey {
+        DbKey::new(Keyspace::Transaction).push(key)
+    }
+
+    pub fn check_store(store: &dyn KvStore) {
+        store.put(&tx(1), b"one").unwrap();
+        store.put(&tx(2), b"two").unwrap();
+        store.put(&DbKey::new(Keyspace::Meta).push("counter"), b"meta").unwrap();
+        assert_eq!(store.get(&tx(1)).unwrap(), Some(b"one".to_vec()));
+        assert_eq!(store.read(&tx(9)), Err(DbError::NotFound(tx(9))));
+
+        store.delete(&tx(1)).unwrap();
+        assert_eq!(store.get(&tx(1)).unwrap(), None);
+
+        let mut batch = WriteBatch::new();
+        batch.put(tx(3), b"three".to_vec())
+            .put(tx(-4), b"minus four".to_vec())
+            .delete(tx(2));
+        store.write_batch(&batch).unwrap();
+
+        let keys: Vec<DbKey> = store.keyspace_entries(Keyspace::Transaction)
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec![tx(-4), tx(3)]);
+        assert_eq!(store.iter_from(&tx(0)).next().map(|(key, _)| key), Some(tx(3)));
+        assert_eq!(store.iter_from(&DbKey::default()).count(), 3);
+
+        store.put(&DbKey::new(Keyspace::Block).push(1u64), b"block").unwrap();
+        let scan = |after: Option<DbKey>, direction| -> Vec<DbKey> {
+            store.scan(Keyspace::Transaction, after.as_ref(), direction)
+                .map(|(key, _)| key)
+                .collect()
+        };
+        assert_eq!(scan(None, Direction::Reverse), vec![tx(3), tx(-4)]);
+        assert_eq!(scan(Some(tx(3)), Direction::Reverse), vec![tx(-4)]);
+        assert_eq!(scan(Some(tx(0)), Direction::Reverse), vec![tx(-4)]);
+        assert_eq!(scan(Some(tx(-4)), Direction::Forward), vec![tx(3)]);
+        assert_eq!(scan(Some(tx(3)), Direction::Forward), Vec::<DbKey>::new());
+        assert_eq!(store.iter_rev_from(&DbKey::keyspace_end(Keyspace::Transaction)).count(), 4);
+        store.delete(&DbKey::new(Keyspace::Block).push(1u64)).unwrap();
+
+        let snapshot = store.snapshot();
+        store.put(&tx(5), b"five").unwrap();
+        store.delete(&tx(3)).unwrap();
+
+        assert_eq!(snapshot.get(&tx(3)).unwrap(), Some(b"three".to_vec()));
+        assert_eq!(snapshot.get(&tx(5)).unwrap(), None);
+        assert_eq!(snapshot.iter_from(&DbKey::default()).count(), 3);
+        assert_eq!(store.get(&tx(3)).unwrap(), None);
+    }
+}
@@ -2,12 +2,12 @@
 
 json_file="tx.json"
 
-# Define variables for the address and port
-address="127.0.0.1"
-port="3699"
+# Address and port of the node, matching GRID_BIND_ADDRESS / GRID_PORT
+address="${GRID_BIND_ADDRESS:-127.0.0.1}"
+port="${GRID_PORT:-3690}"
 
 # Build the curl command with proper quoting
-curl_cmd="curl -X POST -H \"Content-Type: application/json\" -d @$json_file http://$address:$port"
+curl_cmd="curl -X POST -H \"Content-Type: application/json\" -d @$json_file http://$address:$port/transaction/post"
 
 # Execute the curl command
 eval "$curl_cmd"
@@ -0,0 +1,54 @@
+use crate::ledger::Hash;
+
+/// Prefixes that keep a leaf from ever hashing like an inner node.
+const LEAF_PREFIX: &[u8] = &[0x00];
+const NODE_PREFIX: &[u8] = &[0x01];
+
+/// Root of the binary Merkle tree over `leaves`, in order. Each level pairs
+/// neighbours; an odd node out moves up unchanged. The root of no leaves is
+/// `Hash::ZERO`.
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return Hash::ZERO;
+    }
+
+    let mut level: Vec<Hash> = leaves.iter()
+        .map(|leaf| Hash::digest(&[LEAF_PREFIX, &leaf.0]))
+        .collect();
+    while level.len() > 1 {
+        level = level.chunks(2)
+            .map(|pair| match pair {
+                [left, right] => Hash::digest(&[NODE_PREFIX, &left.0, &right.0]),
+                [odd] => *odd,
+                _ => unreachable!("chunks(2) yields one or two nodes"),
+            })
+            .collect();
+    }
+
+    level[0]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::ledger::Hash;
+    use crate::ledger::merkle::merkle_root;
+
+    fn leaves(count: u8) -> Vec<Hash> {
+        (0..count).map(|leaf| Hash::digest(&[&[leaf]])).collect()
+    }
+
+    #[test]
+    fn test_root_commits_to_every_leaf_in_order() {
+        let three = leaves(3);
+        let mut swapped = three.clone();
+        swapped.swap(0, 1);
+
+        assert_eq!(merkle_root(&[]), Hash::ZERO);
+        assert_ne!(merkle_root(&three[..1]), three[0]);
+        assert_ne!(merkle_root(&three), merkle_root(&swapped));
+        assert_ne!(merkle_root(&three), merkle_root(&three[..2]));
+        assert_ne!(merkle_root(&leaves(4)), merkle_root(&three));
+        assert_eq!(merkle_root(&leaves(5)), merkle_root(&leaves(5)));
+    }
+}
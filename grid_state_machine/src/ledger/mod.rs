@@ -0,0 +1,218 @@
+pub mod merkle;
+pub mod producer;
+
+use crate::ledger::merkle::merkle_root;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Prefix of every block hash, so a block hash never collides with a hash
+/// of some other grid structure.
+const BLOCK_DOMAIN: &[u8] = b"zgrid:block:v1:";
+
+/// SHA-256 digest, written as lowercase hex in JSON and URLs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hash(pub [u8; 32]);
+
+impl Hash {
+    pub const ZERO: Hash = Hash([0; 32]);
+
+    /// SHA-256 over `parts`, concatenated.
+    pub fn digest(parts: &[&[u8]]) -> Hash {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        Hash(hasher.finalize().into())
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl FromStr for Hash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|e| e.to_string())?;
+        let bytes: [u8; 32] = bytes.try_into()
+            .map_err(|bytes: Vec<u8>| format!("expected 32 bytes, got {}", bytes.len()))?;
+        Ok(Hash(bytes))
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(D::Error::custom)
+    }
+}
+
+/// One link of the ledger. `hash` covers every field but `transactions`
+/// and `keys`, which `merkle_root` commits to and the store assigns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Block {
+    pub height: u64,
+    pub hash: Hash,
+    /// Hash of the block at `height - 1`; zero for the first block.
+    pub parent_hash: Hash,
+    /// Milliseconds since the Unix epoch; never earlier than the parent's.
+    pub timestamp: u64,
+    /// Hashes of the block's transactions, in the order they were applied.
+    pub transactions: Vec<Hash>,
+    /// Store keys of the transactions, in the same order.
+    pub keys: Vec<i32>,
+    pub merkle_root: Hash,
+    /// Root of the state once the block's transactions are applied.
+    pub state_root: Hash,
+}
+
+impl Block {
+    /// Builds the block that follows `parent`, or the first block when
+    /// there is none.
+    pub fn new(parent: Option<&Block>, timestamp: u64, transactions: Vec<Hash>, keys: Vec<i32>, state_root: Hash) -> Self {
+        let (height, parent_hash, parent_timestamp) = match parent {
+            Some(parent) => (parent.height + 1, parent.hash, parent.timestamp),
+            None => (0, Hash::ZERO, 0),
+        };
+        let merkle_root = merkle_root(&transactions);
+
+        let mut block = Block {
+            height,
+            hash: Hash::ZERO,
+            parent_hash,
+            timestamp: timestamp.max(parent_timestamp),
+            transactions,
+            keys,
+            merkle_root,
+            state_root,
+        };
+        block.hash = block.header_hash();
+        block
+    }
+
+
+    /// Hash of the header: `BLOCK_DOMAIN`, then the big-endian height,
+    /// parent hash, big-endian timestamp and transaction count, Merkle root
+    /// and state root.
+    pub fn header_hash(&self) -> Hash {
+        Hash::digest(&[
+            BLOCK_DOMAIN,
+            &self.height.to_be_bytes(),
+            &self.parent_hash.0,
+            &self.timestamp.to_be_bytes(),
+            &(self.transactions.len() as u64).to_be_bytes(),
+            &self.merkle_root.0,
+            &self.state_root.0,
+        ])
+    }
+}
+
+/// How `GET /block/{id}` names a block: `latest`, a height or a hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockId {
+    Latest,
+    Height(u64),
+    Hash(Hash),
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockId::Latest => write!(f, "latest"),
+            BlockId::Height(height) => write!(f, "{}", height),
+            BlockId::Hash(hash) => write!(f, "{}", hash),
+        }
+    }
+}
+
+impl FromStr for BlockId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "latest" {
+            Ok(BlockId::Latest)
+        } else if s.len() == 64 {
+            s.parse().map(BlockId::Hash)
+        } else {
+            s.parse().map(BlockId::Height).map_err(|_| format!("{} is not a block height or hash", s))
+        }
+    }
+}
+
+
+/// Milliseconds since the Unix epoch by the local clock, for block
+/// timestamps.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::ledger::{Block, BlockId, Hash};
+    use crate::transaction::testing::signed;
+
+    #[test]
+    fn test_blocks_link_to_their_parent() {
+        let first = Block::new(None, 2_000, vec![signed(1, 0, "a").hash().unwrap()], vec![1], Hash::digest(&[b"1"]));
+        let second = Block::new(Some(&first), 1_000, vec![signed(1, 1, "b").hash().unwrap()], vec![2], Hash::digest(&[b"2"]));
+        let mut tampered = second.clone();
+        tampered.merkle_root = first.merkle_root;
+        let mut restated = second.clone();
+        restated.state_root = first.state_root;
+
+        assert_eq!(first.height, 0);
+        assert_eq!(first.parent_hash, Hash::ZERO);
+        assert_eq!(second.height, 1);
+        assert_eq!(second.parent_hash, first.hash);
+        assert_eq!(second.timestamp, 2_000);
+        assert_eq!(second.header_hash(), second.hash);
+        assert_ne!(tampered.header_hash(), second.hash);
+        assert_ne!(restated.header_hash(), second.hash);
+    }
+
+    #[test]
+    fn test_hashes_and_block_ids_parse() {
+        let hash = Hash::digest(&[b"meow"]);
+        let json = serde_json::to_string(&hash).unwrap();
+
+        assert_eq!(json.len(), 64 + 2);
+        assert_eq!(serde_json::from_str::<Hash>(&json).unwrap(), hash);
+        assert!(serde_json::from_str::<Hash>("\"abcd\"").is_err());
+        assert_eq!("latest".parse(), Ok(BlockId::Latest));
+        assert_eq!("42".parse(), Ok(BlockId::Height(42)));
+        assert_eq!(hash.to_string().parse(), Ok(BlockId::Hash(hash)));
+        assert!("-1".parse::<BlockId>().is_err());
+        assert!("z".repeat(64).parse::<BlockId>().is_err());
+    }
+
+    #[test]
+    fn test_transaction_hash_covers_the_signature() {
+        let transaction = signed(1, 0, "a");
+        let mut resigned = transaction.clone();
+        resigned.signature = signed(2, 0, "a").signature;
+
+        assert_eq!(transaction.hash(), signed(1, 0, "a").hash());
+        assert_ne!(transaction.hash(), signed(1, 1, "a").hash());
+        assert_ne!(transaction.hash(), resigned.hash());
+    }
+}
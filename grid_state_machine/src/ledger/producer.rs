@@ -0,0 +1,154 @@
+use crate::ledger::{now_millis, Block};
+use crate::mempool::Mempool;
+use crate::repository::RepositoryError;
+use crate::repository::blocking::AsyncRepository;
+
+use log::{error, info};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Most transactions one block holds.
+pub const MAX_BLOCK_TRANSACTIONS: usize = 1000;
+
+/// Produces a block from the mempool every `interval`, forever. Intervals
+/// with nothing ready produce no block.
+pub async fn run_producer(mempool: Arc<Mempool>, repository: AsyncRepository, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        match produce_block(&mempool, &repository).await {
+            Ok(Some(block)) => info!(
+                "Ledger: Block {} with {} transaction(s) :: {}", block.height, block.transactions.len(), block.hash
+            ),
+            Ok(None) => {}
+            Err(e) => error!("Ledger: Failed to produce a block: {}", e),
+        }
+    }
+}
+
+
+/// Takes up to `MAX_BLOCK_TRANSACTIONS` ready transactions from `mempool`
+/// and stores them as the next block. Returns `None` when nothing is ready.
+/// Transactions the block leaves out are recorded as dropped in `mempool`;
+/// if the write fails, every taken transaction goes back to it.
+pub async fn produce_block(mempool: &Arc<Mempool>, repository: &AsyncRepository) -> Result<Option<Block>, RepositoryError> {
+    let mempool = Arc::clone(mempool);
+    let timestamp = now_millis();
+
+    repository.run(move |repository| {
+        let ready = mempool.take_ready(MAX_BLOCK_TRANSACTIONS, |sender| repository.next_nonce(sender))?;
+        if ready.is_empty() {
+            return Ok(None);
+        }
+
+        match repository.append_block(&ready, timestamp) {
+            Ok(appended) => {
+                for (transaction, e) in &appended.dropped {
+                    mempool.record_dropped(transaction, drop_reason(e));
+                }
+                Ok(appended.block)
+            }
+            Err(e) => {
+                mempool.requeue(ready);
+                Err(e)
+            }
+        }
+    }).await
+}
+
+
+/// Reason shown to the sender of a transaction left out of a block.
+fn drop_reason(error: &RepositoryError) -> String {
+    match error {
+        RepositoryError::StaleNonce { nonce, expected, .. } => {
+            format!("nonce {} was already used, next is {}", nonce, expected)
+        }
+        RepositoryError::NonceGap { nonce, expected, .. } => format!("nonce {} skips ahead of {}", nonce, expected),
+        RepositoryError::InvalidSender(_) => "invalid sender key".to_string(),
+        e => e.to_string(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::db::keys::{DbKey, Keyspace};
+    use crate::db::memory::MemoryStore;
+    use crate::db::store::KvStore;
+    use crate::ledger::producer::produce_block;
+    use crate::mempool::{Mempool, MempoolConfig, NonceGapPolicy};
+    use crate::repository::{Repository, RepositoryError};
+    use crate::repository::blocking::AsyncRepository;
+    use crate::transaction::testing::{signed, with_fee};
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_blocks_drain_ready_transactions() {
+        let repository = Arc::new(Repository::new(MemoryStore::new()));
+        let async_repository = AsyncRepository::new(Arc::clone(&repository), 2, Duration::from_secs(5));
+        let mempool = Arc::new(Mempool::new(MempoolConfig {
+            capacity: 10,
+            per_sender: 10,
+            ttl: Duration::from_secs(60),
+            nonce_gap: NonceGapPolicy::Hold { max_gap: 4 },
+        }));
+
+        mempool.submit(with_fee(1, 0, 1, "cheap"), 0).unwrap();
+        mempool.submit(with_fee(2, 0, 9, "dear"), 0).unwrap();
+        mempool.submit(signed(2, 2, "waiting"), 0).unwrap();
+        let first = produce_block(&mempool, &async_repository).await.unwrap().unwrap();
+
+        mempool.submit(signed(2, 1, "gap filler"), 1).unwrap();
+        let second = produce_block(&mempool, &async_repository).await.unwrap().unwrap();
+        let idle = produce_block(&mempool, &async_repository).await;
+
+        assert_eq!(first.height, 0);
+        assert_eq!(first.keys, vec![1, 2]);
+        assert_eq!(first.transactions[0], with_fee(2, 0, 9, "dear").hash().unwrap());
+        assert_eq!(repository.read_transaction(&1).map(|stored| stored.transaction.payload), Ok("dear".to_string()));
+        assert_eq!(second.height, 1);
+        assert_eq!(second.parent_hash, first.hash);
+        assert_eq!(second.keys, vec![3, 4]);
+        assert_eq!(repository.read_transaction(&4).map(|stored| stored.transaction.payload), Ok("waiting".to_string()));
+        assert_eq!(repository.next_nonce(&signed(2, 0, "").sender), Ok(3));
+        assert_eq!(idle, Ok(None));
+        assert!(mempool.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_blocks_requeue_and_drops_are_recorded() {
+        let store = Arc::new(MemoryStore::new());
+        let repository = Arc::new(Repository::new(Arc::clone(&store)));
+        let async_repository = AsyncRepository::new(Arc::clone(&repository), 2, Duration::from_secs(5));
+        let mempool = Arc::new(Mempool::new(MempoolConfig {
+            capacity: 10,
+            per_sender: 10,
+            ttl: Duration::from_secs(60),
+            nonce_gap: NonceGapPolicy::Reject,
+        }));
+        let chain_head = DbKey::new(Keyspace::Meta).push("chain_head");
+
+        mempool.submit(signed(1, 0, "kept"), 0).unwrap();
+        mempool.submit(signed(2, 0, "superseded"), 0).unwrap();
+        repository.insert_transaction(&signed(2, 0, "stored first")).unwrap();
+        store.put(&chain_head, b"not a height").unwrap();
+        let failed = produce_block(&mempool, &async_repository).await;
+        let requeued = mempool.pending_nonces(&signed(1, 0, "").sender);
+
+        store.delete(&chain_head).unwrap();
+        let retried = produce_block(&mempool, &async_repository).await.unwrap().unwrap();
+
+        assert!(matches!(failed, Err(RepositoryError::CorruptBlock { .. })));
+        assert_eq!(requeued, vec![0]);
+        assert_eq!(retried.transactions, vec![signed(1, 0, "kept").hash().unwrap()]);
+        let dropped = mempool.dropped(&signed(2, 0, "").sender);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].nonce, 0);
+        assert_eq!(dropped[0].reason, "nonce 0 was already used, next is 1");
+        assert!(mempool.dropped(&signed(1, 0, "").sender).is_empty());
+    }
+}
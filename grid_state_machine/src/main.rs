@@ -1,16 +1,170 @@
+mod config;
 mod db;
 mod api;
+mod ledger;
+mod mempool;
 mod repository;
+mod state;
+mod transaction;
 
-use db::DatabaseState;
+use config::{Command, Config, StorageBackend};
+use db::{DatabaseState, DbError};
+use db::memory::MemoryStore;
 use api::{start_server};
-use repository::Repository;
+use ledger::Hash;
+use ledger::producer::run_producer;
+use mempool::Mempool;
+use repository::{Repository, RepositoryError};
+use repository::blocking::AsyncRepository;
+use state::smt::{verify_proof, StateProof};
+use transaction::Transaction;
+use libp2p_identity::Keypair;
+use log::LevelFilter;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[tokio::main]
 async fn main() {
-    let db_path: String = "./grid_db".to_string();
-    let db_state: DatabaseState = DatabaseState::init(db_path);
-    let arc_repository = Arc::new(Repository::new(db_state));
-    let _server_result = start_server(arc_repository).await;
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Info)
+        .parse_default_env()
+        .init();
+
+    let (config, command) = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(Command::Sign { key, nonce, fee, payload }) = command {
+        match sign(&key, nonce, fee, payload) {
+            Ok(signed) => println!("{}", signed),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(Command::Verify { proof, root }) = command {
+        match verify(&proof, &root) {
+            Ok(()) => println!("Verify: Proof holds against {}", root),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let arc_repository = match open_repository(&config) {
+        Ok(repository) => Arc::new(repository),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match arc_repository.migrate_legacy_keys() {
+        Ok(0) => {}
+        Ok(moved) => println!("Database :: Migrated {} legacy record(s)", moved),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(Command::Scan { repair }) = command {
+        if let Err(e) = scan(&arc_repository, repair) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let repository = AsyncRepository::new(arc_repository, config.storage_concurrency, config.storage_queue_timeout());
+    let mempool = Arc::new(Mempool::new(config.mempool_config()));
+    tokio::spawn(run_producer(Arc::clone(&mempool), repository.clone(), config.block_interval()));
+
+    if let Err(e) = start_server(repository, mempool, config.socket_addr()).await {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+
+fn open_repository(config: &Config) -> Result<Repository, DbError> {
+    match config.storage {
+        StorageBackend::Leveldb => {
+            let db_state = DatabaseState::open(config.db_path.clone(), config.durability())?;
+            println!("Database :: {} ({} writes)", db_state.db_path_string, config.durability().name());
+            Ok(Repository::new(db_state))
+        }
+        StorageBackend::Memory => {
+            println!("Database :: in-memory, nothing is persisted");
+            Ok(Repository::new(MemoryStore::new()))
+        }
+    }
+}
+
+
+/// Signs a transaction with the keypair at `key_path`, timestamped now, and
+/// returns it as JSON.
+fn sign(key_path: &Path, nonce: u64, fee: u64, payload: String) -> Result<String, String> {
+    let bytes = std::fs::read(key_path)
+        .map_err(|e| format!("Sign: Failed to read {}: {}", key_path.display(), e))?;
+    let keypair = Keypair::from_protobuf_encoding(&bytes)
+        .map_err(|e| format!("Sign: Invalid key file {}: {}", key_path.display(), e))?;
+    if keypair.clone().try_into_ed25519().is_err() {
+        return Err(format!("Sign: {} does not hold an ed25519 key", key_path.display()));
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("Sign: Clock is before the Unix epoch: {}", e))?
+        .as_millis() as u64;
+
+    let transaction = Transaction::sign(&keypair, nonce, fee, timestamp, payload)
+        .map_err(|e| format!("Sign: {}", e))?;
+    serde_json::to_string_pretty(&transaction)
+        .map_err(|e| format!("Sign: {}", e))
+}
+
+
+/// Checks the proof in the file at `proof_path` against `root`. The file
+/// holds a reply read with `?prove=true` or just its `proof` object.
+fn verify(proof_path: &Path, root: &Hash) -> Result<(), String> {
+    let bytes = std::fs::read(proof_path)
+        .map_err(|e| format!("Verify: Failed to read {}: {}", proof_path.display(), e))?;
+    let mut reply: serde_json::Value = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("Verify: {} is not JSON: {}", proof_path.display(), e))?;
+    let proof = match reply.get_mut("proof") {
+        Some(proof) => proof.take(),
+        None => reply,
+    };
+    let proof: StateProof = serde_json::from_value(proof)
+        .map_err(|e| format!("Verify: {} holds no state proof: {}", proof_path.display(), e))?;
+
+    verify_proof(root, &proof).map_err(|e| e.to_string())
+}
+
+
+/// Prints every corrupt record and, with `repair`, deletes them. Repair is
+/// refused once the ledger holds a block, since it would move the root.
+fn scan(repository: &Repository, repair: bool) -> Result<(), RepositoryError> {
+    let corrupt = repository.scan_corrupt();
+
+    for record in &corrupt {
+        println!("Scan: Key {} is corrupt: {}", record.key, record.reason);
+        if repair {
+            repository.remove_transaction(&record.key)?;
+            println!("Scan: Key {} removed", record.key);
+        }
+    }
+    println!("Scan: {} corrupt record(s) found", corrupt.len());
+
+    Ok(())
 }
@@ -0,0 +1,662 @@
+use crate::transaction::Transaction;
+
+use serde::Serialize;
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{btree_map, BTreeMap, BinaryHeap, VecDeque};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Most dropped transactions remembered for `Mempool::dropped`, across
+/// all senders.
+const DROP_LOG_LEN: usize = 1000;
+
+/// What to do with a transaction whose nonce skips ahead of the sender's
+/// next nonce, counting the sender's pending transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonceGapPolicy {
+    /// Refuse it; the sender resubmits once the gap is filled.
+    #[default]
+    Reject,
+    /// Keep it pending, up to `max_gap` nonces ahead, until the transactions
+    /// before it arrive.
+    Hold { max_gap: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolConfig {
+    /// Pending transactions across all senders.
+    pub capacity: usize,
+    /// Pending transactions of one sender.
+    pub per_sender: usize,
+    /// How long a transaction may stay pending before it is dropped.
+    pub ttl: Duration,
+    pub nonce_gap: NonceGapPolicy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MempoolError {
+    /// The nonce was already committed.
+    Stale { nonce: u64, expected: u64 },
+    /// The nonce is already pending with an equal or higher fee.
+    Duplicate { nonce: u64, fee: u64 },
+    /// The nonce skips ahead of `expected` further than the policy allows.
+    Gap { nonce: u64, expected: u64 },
+    /// The sender already has `limit` pending transactions.
+    SenderFull { limit: usize },
+    /// The pool is full and nothing pending pays less.
+    Full,
+}
+
+impl fmt::Display for MempoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MempoolError::Stale { nonce, expected } => {
+                write!(f, "Mempool: Nonce {} is already committed, next is {}", nonce, expected)
+            }
+            MempoolError::Duplicate { nonce, fee } => {
+                write!(f, "Mempool: Nonce {} is already pending with fee {}", nonce, fee)
+            }
+            MempoolError::Gap { nonce, expected } => {
+                write!(f, "Mempool: Nonce {} skips ahead of {}", nonce, expected)
+            }
+            MempoolError::SenderFull { limit } => {
+                write!(f, "Mempool: Sender already has {} pending transactions", limit)
+            }
+            MempoolError::Full => write!(f, "Mempool: Pool is full"),
+        }
+    }
+}
+
+impl std::error::Error for MempoolError {}
+
+/// A pending transaction as shown by `GET /mempool`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PendingTransaction {
+    pub sender: String,
+    pub nonce: u64,
+    pub fee: u64,
+    pub payload: String,
+    pub age_ms: u64,
+}
+
+/// An admitted transaction that left the mempool without being committed,
+/// as shown by `GET /account/{pubkey}/nonce`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DroppedTransaction {
+    pub nonce: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    transaction: Transaction,
+    received: Instant,
+    /// Arrival order, the tie-break between equal fees.
+    sequence: u64,
+}
+
+impl Entry {
+    /// Higher fee first, then earlier arrival.
+    fn priority(&self) -> (u64, Reverse<u64>) {
+        (self.transaction.fee, Reverse(self.sequence))
+    }
+}
+
+/// What one `admit` changed, so `submit_all` can undo it.
+struct Admitted {
+    sender: String,
+    nonce: u64,
+    /// The lower-fee entry the admitted transaction replaced.
+    replaced: Option<Entry>,
+    /// The entry evicted to make room, with its sender.
+    evicted: Option<(String, Entry)>,
+    /// Whether the replaced or evicted entry was recorded as dropped, and
+    /// the oldest drop that record pushed out of the log.
+    logged: Option<Option<(String, DroppedTransaction)>>,
+}
+
+#[derive(Debug, Default)]
+struct Pool {
+    /// Pending transactions by sender, then nonce.
+    senders: BTreeMap<String, BTreeMap<u64, Entry>>,
+    /// Each sender's nonce after those the last `take_ready` handed out,
+    /// which may not be stored yet.
+    taken: BTreeMap<String, u64>,
+    len: usize,
+    next_sequence: u64,
+    /// The latest `DROP_LOG_LEN` drops, oldest first, with their senders.
+    dropped: VecDeque<(String, DroppedTransaction)>,
+}
+
+impl Pool {
+    /// Appends a drop to the log and returns the oldest one if the log was
+    /// full and it had to go.
+    fn record_dropped(&mut self, sender: &str, nonce: u64, reason: String) -> Option<(String, DroppedTransaction)> {
+        let forgotten = if self.dropped.len() == DROP_LOG_LEN {
+            self.dropped.pop_front()
+        } else {
+            None
+        };
+        self.dropped.push_back((sender.to_string(), DroppedTransaction { nonce, reason }));
+        forgotten
+    }
+}
+
+/// Bounded staging area between the API and storage. Transactions wait here
+/// until the block producer takes the ready ones, highest fee first; a
+/// transaction is ready once every earlier nonce of its sender is committed
+/// or taken with it.
+pub struct Mempool {
+    config: MempoolConfig,
+    pool: Mutex<Pool>,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool { config, pool: Mutex::new(Pool::default()) }
+    }
+
+
+    pub fn capacity(&self) -> usize {
+        self.config.capacity
+    }
+
+
+    /// Adds `transaction`, whose sender's next committed nonce is
+    /// `committed`; nonces the block producer has taken count as committed.
+    /// A pending transaction with the same nonce is replaced
+    /// only by a higher fee. When the pool is full, the cheapest last-in-line
+    /// transaction of another sender is evicted if it pays less.
+    pub fn submit(&self, transaction: Transaction, committed: u64) -> Result<(), MempoolError> {
+        let mut pool = self.lock_pool();
+        self.expire(&mut pool, Instant::now());
+        self.admit(&mut pool, transaction, committed).map(|_| ())
+    }
+
+
+    /// Adds every transaction or none. `committed` gives each sender's next
+    /// committed nonce. On failure the transactions admitted so far are
+    /// taken out again and whatever they replaced or evicted is put back.
+    pub fn submit_all(
+        &self,
+        transactions: Vec<Transaction>,
+        committed: &BTreeMap<String, u64>,
+    ) -> Result<(), (usize, MempoolError)> {
+        let mut pool = self.lock_pool();
+        self.expire(&mut pool, Instant::now());
+
+        let mut admitted = Vec::with_capacity(transactions.len());
+        for (index, transaction) in transactions.into_iter().enumerate() {
+            let base = committed.get(&transaction.sender).copied().unwrap_or(0);
+            match self.admit(&mut pool, transaction, base) {
+                Ok(change) => admitted.push(change),
+                Err(e) => {
+                    for change in admitted.into_iter().rev() {
+                        undo(&mut pool, change);
+                    }
+                    return Err((index, e));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+
+    /// Removes and returns up to `limit` ready transactions, highest fee
+    /// first while keeping each sender's nonces in order. `committed` gives
+    /// a sender's next committed nonce; anything pending below it is dropped.
+    pub fn take_ready<E>(
+        &self,
+        limit: usize,
+        mut committed: impl FnMut(&str) -> Result<u64, E>,
+    ) -> Result<Vec<Transaction>, E> {
+        let mut pool = self.lock_pool();
+        self.expire(&mut pool, Instant::now());
+        pool.taken.clear();
+
+        let mut heads = BinaryHeap::new();
+        let senders: Vec<String> = pool.senders.keys().cloned().collect();
+        for sender in senders {
+            let base = committed(&sender)?;
+            let pending = pool.senders.get_mut(&sender).expect("sender listed above");
+            let current = pending.split_off(&base);
+            let stale = std::mem::replace(pending, current);
+            pool.len -= stale.len();
+            for nonce in stale.into_keys() {
+                pool.record_dropped(&sender, nonce, format!("nonce {} was already used, next is {}", nonce, base));
+            }
+            if let Some(entry) = pool.senders[&sender].get(&base) {
+                heads.push(Head { priority: entry.priority(), sender: sender.clone(), nonce: base });
+            }
+        }
+
+        let mut ready = Vec::new();
+        while ready.len() < limit {
+            let Some(head) = heads.pop() else {
+                break;
+            };
+            let pending = pool.senders.get_mut(&head.sender).expect("head sender is pending");
+            let entry = pending.remove(&head.nonce).expect("head nonce is pending");
+            if let Some(next) = pending.get(&(head.nonce + 1)) {
+                heads.push(Head { priority: next.priority(), sender: head.sender.clone(), nonce: head.nonce + 1 });
+            }
+            pool.len -= 1;
+            pool.taken.insert(head.sender, head.nonce + 1);
+            ready.push(entry.transaction);
+        }
+        pool.senders.retain(|_, pending| !pending.is_empty());
+
+        Ok(ready)
+    }
+
+
+    /// Every pending transaction, highest priority first.
+    pub fn pending(&self) -> Vec<PendingTransaction> {
+        let now = Instant::now();
+        let mut pool = self.lock_pool();
+        self.expire(&mut pool, now);
+
+        let mut entries: Vec<&Entry> = pool.senders.values().flat_map(|pending| pending.values()).collect();
+        entries.sort_by_key(|entry| Reverse(entry.priority()));
+        entries.into_iter()
+            .map(|entry| PendingTransaction {
+                sender: entry.transaction.sender.clone(),
+                nonce: entry.transaction.nonce,
+                fee: entry.transaction.fee,
+                payload: entry.transaction.payload.clone(),
+                age_ms: now.duration_since(entry.received).as_millis() as u64,
+            })
+            .collect()
+    }
+
+
+    /// Puts transactions back that `take_ready` handed out but that were not
+    /// stored, e.g. because the block write failed, so they are retried.
+    /// They count as just received and may push the pool past its capacity.
+    pub fn requeue(&self, transactions: Vec<Transaction>) {
+        let mut pool = self.lock_pool();
+        for transaction in transactions {
+            pool.taken.remove(&transaction.sender);
+            let entry = Entry { transaction, received: Instant::now(), sequence: pool.next_sequence };
+            pool.next_sequence += 1;
+            let pending = pool.senders.entry(entry.transaction.sender.clone()).or_default();
+            if let btree_map::Entry::Vacant(slot) = pending.entry(entry.transaction.nonce) {
+                slot.insert(entry);
+                pool.len += 1;
+            }
+        }
+    }
+
+
+    /// Records that `transaction`, taken for a block, was left out of it.
+    pub fn record_dropped(&self, transaction: &Transaction, reason: String) {
+        self.lock_pool().record_dropped(&transaction.sender, transaction.nonce, reason);
+    }
+
+
+    /// The recent drops of `sender`, oldest first.
+    pub fn dropped(&self, sender: &str) -> Vec<DroppedTransaction> {
+        let mut pool = self.lock_pool();
+        self.expire(&mut pool, Instant::now());
+        pool.dropped.iter()
+            .filter(|(dropped_sender, _)| dropped_sender == sender)
+            .map(|(_, dropped)| dropped.clone())
+            .collect()
+    }
+
+
+    /// Pending nonces of `sender`, in order.
+    pub fn pending_nonces(&self, sender: &str) -> Vec<u64> {
+        let mut pool = self.lock_pool();
+        self.expire(&mut pool, Instant::now());
+        pool.senders.get(sender)
+            .map(|pending| pending.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+
+    fn admit(&self, pool: &mut Pool, transaction: Transaction, committed: u64) -> Result<Admitted, MempoolError> {
+        let committed = committed.max(pool.taken.get(&transaction.sender).copied().unwrap_or(0));
+        let nonce = transaction.nonce;
+        if nonce < committed {
+            return Err(MempoolError::Stale { nonce, expected: committed });
+        }
+
+        let pending = pool.senders.get(&transaction.sender);
+        if let Some(existing) = pending.and_then(|pending| pending.get(&nonce)) {
+            if transaction.fee <= existing.transaction.fee {
+                return Err(MempoolError::Duplicate { nonce, fee: existing.transaction.fee });
+            }
+            let sequence = existing.sequence;
+            let sender = transaction.sender.clone();
+            let reason = format!("replaced by a fee of {}", transaction.fee);
+            let entry = Entry { transaction, received: Instant::now(), sequence };
+            let replaced = pool.senders.get_mut(&sender)
+                .expect("sender has a pending transaction")
+                .insert(nonce, entry);
+            let logged = Some(pool.record_dropped(&sender, nonce, reason));
+            return Ok(Admitted { sender, nonce, replaced, evicted: None, logged });
+        }
+
+        let mut expected = committed;
+        while pending.is_some_and(|pending| pending.contains_key(&expected)) {
+            expected += 1;
+        }
+        let allowed_gap = match self.config.nonce_gap {
+            NonceGapPolicy::Reject => 0,
+            NonceGapPolicy::Hold { max_gap } => max_gap,
+        };
+        if nonce - expected > allowed_gap {
+            return Err(MempoolError::Gap { nonce, expected });
+        }
+        if pending.map_or(0, |pending| pending.len()) >= self.config.per_sender {
+            return Err(MempoolError::SenderFull { limit: self.config.per_sender });
+        }
+        let evicted = if pool.len >= self.config.capacity {
+            Some(evict_cheapest(pool, &transaction)?)
+        } else {
+            None
+        };
+        let logged = evicted.as_ref().map(|(sender, entry)| {
+            let reason = format!("evicted from the full mempool by a fee of {}", transaction.fee);
+            pool.record_dropped(sender, entry.transaction.nonce, reason)
+        });
+
+        let sender = transaction.sender.clone();
+        let entry = Entry { transaction, received: Instant::now(), sequence: pool.next_sequence };
+        pool.next_sequence += 1;
+        pool.len += 1;
+        pool.senders.entry(sender.clone()).or_default().insert(nonce, entry);
+
+        Ok(Admitted { sender, nonce, replaced: None, evicted, logged })
+    }
+
+
+    /// Drops transactions pending longer than the TTL, together with the
+    /// later nonces of their sender, which could never become ready.
+    fn expire(&self, pool: &mut Pool, now: Instant) {
+        let ttl = self.config.ttl;
+        let mut expired = Vec::new();
+        for (sender, pending) in pool.senders.iter_mut() {
+            let first_expired = pending.iter()
+                .find(|(_, entry)| now.duration_since(entry.received) >= ttl)
+                .map(|(nonce, _)| *nonce);
+            if let Some(first) = first_expired {
+                expired.extend(pending.split_off(&first).into_keys().map(|nonce| (sender.clone(), first, nonce)));
+            }
+        }
+        if !expired.is_empty() {
+            for (sender, first, nonce) in &expired {
+                let reason = if nonce == first {
+                    "expired".to_string()
+                } else {
+                    format!("nonce {} before it expired", first)
+                };
+                pool.record_dropped(sender, *nonce, reason);
+            }
+            pool.senders.retain(|_, pending| !pending.is_empty());
+            pool.len -= expired.len();
+        }
+    }
+
+
+    fn lock_pool(&self) -> MutexGuard<'_, Pool> {
+        self.pool.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+
+/// Evicts the lowest-priority transaction that is last in its sender's
+/// line, so no sender is left with a gap, if it pays less than `incoming`,
+/// and returns it with its sender. The incoming sender's own transactions
+/// are never evicted for it.
+fn evict_cheapest(pool: &mut Pool, incoming: &Transaction) -> Result<(String, Entry), MempoolError> {
+    let cheapest = pool.senders.iter()
+        .filter(|(sender, _)| **sender != incoming.sender)
+        .filter_map(|(sender, pending)| pending.last_key_value().map(|(nonce, entry)| (sender, *nonce, entry)))
+        .min_by_key(|(_, _, entry)| entry.priority())
+        .map(|(sender, nonce, entry)| (sender.clone(), nonce, entry.transaction.fee));
+
+    match cheapest {
+        Some((sender, nonce, fee)) if fee < incoming.fee => {
+            let pending = pool.senders.get_mut(&sender).expect("evicted sender is pending");
+            let entry = pending.remove(&nonce).expect("evicted nonce is pending");
+            if pending.is_empty() {
+                pool.senders.remove(&sender);
+            }
+            pool.len -= 1;
+            Ok((sender, entry))
+        }
+        _ => Err(MempoolError::Full),
+    }
+}
+
+
+/// Reverts one `admit`. Changes must be undone newest first, since a later
+/// admission may have evicted an earlier one.
+fn undo(pool: &mut Pool, change: Admitted) {
+    let pending = pool.senders.get_mut(&change.sender).expect("admitted sender is pending");
+    pending.remove(&change.nonce);
+    match change.replaced {
+        Some(replaced) => {
+            pending.insert(change.nonce, replaced);
+        }
+        None => pool.len -= 1,
+    }
+    if pending.is_empty() {
+        pool.senders.remove(&change.sender);
+    }
+    if let Some((sender, entry)) = change.evicted {
+        pool.senders.entry(sender).or_default().insert(entry.transaction.nonce, entry);
+        pool.len += 1;
+    }
+    if let Some(forgotten) = change.logged {
+        pool.dropped.pop_back();
+        if let Some(forgotten) = forgotten {
+            pool.dropped.push_front(forgotten);
+        }
+    }
+}
+
+
+/// Next ready transaction of one sender, ordered by priority in `take_ready`.
+#[derive(Debug, PartialEq, Eq)]
+struct Head {
+    priority: (u64, Reverse<u64>),
+    sender: String,
+    nonce: u64,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority)
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::mempool::{DroppedTransaction, Mempool, MempoolConfig, MempoolError, NonceGapPolicy};
+    use crate::transaction::testing::{signed, with_fee};
+
+    use std::collections::BTreeMap;
+    use std::convert::Infallible;
+    use std::time::Duration;
+
+    fn init_mempool(capacity: usize, per_sender: usize, nonce_gap: NonceGapPolicy) -> Mempool {
+        Mempool::new(MempoolConfig { capacity, per_sender, ttl: Duration::from_secs(60), nonce_gap })
+    }
+
+    fn nothing_committed(_: &str) -> Result<u64, Infallible> {
+        Ok(0)
+    }
+
+    fn payloads(transactions: &[crate::transaction::Transaction]) -> Vec<&str> {
+        transactions.iter().map(|transaction| transaction.payload.as_str()).collect()
+    }
+
+    #[test]
+    fn test_ready_transactions_come_out_by_fee_in_nonce_order() {
+        let mempool = init_mempool(10, 10, NonceGapPolicy::Reject);
+
+        mempool.submit(with_fee(1, 0, 1, "a0"), 0).unwrap();
+        mempool.submit(with_fee(1, 1, 50, "a1"), 0).unwrap();
+        mempool.submit(with_fee(2, 0, 10, "b0"), 0).unwrap();
+        mempool.submit(with_fee(3, 0, 10, "c0"), 0).unwrap();
+
+        let first = mempool.take_ready(3, nothing_committed).unwrap();
+        // a0 is taken but not stored yet, so a2 follows on from a1.
+        let in_flight = mempool.submit(signed(1, 2, "a2"), 0);
+        let rest = mempool.take_ready(10, |_| Ok::<u64, Infallible>(1)).unwrap();
+
+        assert_eq!(payloads(&first), vec!["b0", "c0", "a0"]);
+        assert_eq!(in_flight, Ok(()));
+        assert_eq!(payloads(&rest), vec!["a1", "a2"]);
+        assert!(mempool.pending().is_empty());
+    }
+
+    #[test]
+    fn test_duplicates_stale_nonces_and_gaps_are_refused() {
+        let mempool = init_mempool(10, 2, NonceGapPolicy::Reject);
+
+        mempool.submit(with_fee(1, 3, 5, "first"), 3).unwrap();
+        let duplicate = mempool.submit(with_fee(1, 3, 5, "again"), 3);
+        let replaced = mempool.submit(with_fee(1, 3, 6, "higher fee"), 3);
+        let stale = mempool.submit(signed(1, 2, "old"), 3);
+        let gap = mempool.submit(signed(1, 5, "early"), 3);
+        mempool.submit(signed(1, 4, "next"), 3).unwrap();
+        let sender_full = mempool.submit(signed(1, 5, "one more"), 3);
+
+        assert_eq!(duplicate, Err(MempoolError::Duplicate { nonce: 3, fee: 5 }));
+        assert_eq!(replaced, Ok(()));
+        assert_eq!(stale, Err(MempoolError::Stale { nonce: 2, expected: 3 }));
+        assert_eq!(gap, Err(MempoolError::Gap { nonce: 5, expected: 4 }));
+        assert_eq!(sender_full, Err(MempoolError::SenderFull { limit: 2 }));
+        assert_eq!(mempool.pending()[0].payload, "higher fee");
+        assert_eq!(mempool.pending_nonces(&signed(1, 0, "").sender), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_held_nonces_wait_for_the_gap() {
+        let mempool = init_mempool(10, 10, NonceGapPolicy::Hold { max_gap: 2 });
+
+        mempool.submit(signed(1, 2, "third"), 0).unwrap();
+        let too_far = mempool.submit(signed(1, 3, "fourth"), 0);
+        let waiting = mempool.take_ready(10, nothing_committed).unwrap();
+        mempool.submit(signed(1, 0, "first"), 0).unwrap();
+        mempool.submit(signed(1, 1, "second"), 0).unwrap();
+        let ready = mempool.take_ready(10, nothing_committed).unwrap();
+
+        assert_eq!(too_far, Err(MempoolError::Gap { nonce: 3, expected: 0 }));
+        assert!(waiting.is_empty());
+        assert_eq!(payloads(&ready), vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    fn test_full_pool_evicts_the_cheapest_tail() {
+        let mempool = init_mempool(3, 10, NonceGapPolicy::Reject);
+
+        mempool.submit(with_fee(1, 0, 5, "a0"), 0).unwrap();
+        mempool.submit(with_fee(1, 1, 1, "a1"), 0).unwrap();
+        mempool.submit(with_fee(2, 0, 3, "b0"), 0).unwrap();
+        let evicting = mempool.submit(with_fee(3, 0, 2, "c0"), 0);
+        let too_cheap = mempool.submit(with_fee(4, 0, 1, "d0"), 0);
+        let own_tail = mempool.submit(with_fee(3, 1, 9, "c1"), 0);
+
+        assert_eq!(evicting, Ok(()));
+        assert_eq!(too_cheap, Err(MempoolError::Full));
+        assert_eq!(own_tail, Ok(()));
+        let mut left: Vec<String> = mempool.pending().into_iter().map(|pending| pending.payload).collect();
+        left.sort();
+        assert_eq!(left, vec!["a0", "c0", "c1"]);
+        assert_eq!(mempool.dropped(&signed(1, 0, "").sender), vec![DroppedTransaction {
+            nonce: 1,
+            reason: "evicted from the full mempool by a fee of 2".to_string(),
+        }]);
+        assert_eq!(mempool.dropped(&signed(2, 0, "").sender), vec![DroppedTransaction {
+            nonce: 0,
+            reason: "evicted from the full mempool by a fee of 9".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_submit_all_is_all_or_nothing_and_entries_expire() {
+        let mempool = Mempool::new(MempoolConfig {
+            capacity: 10,
+            per_sender: 10,
+            ttl: Duration::from_millis(30),
+            nonce_gap: NonceGapPolicy::Reject,
+        });
+        let committed = BTreeMap::new();
+
+        let refused = mempool.submit_all(vec![signed(1, 0, "a"), signed(1, 2, "gap")], &committed);
+        let pending_after_refusal = mempool.pending().len();
+        let accepted = mempool.submit_all(vec![signed(1, 0, "a"), signed(2, 0, "b")], &committed);
+        let pending_after_accept = mempool.pending().len();
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(refused, Err((1, MempoolError::Gap { nonce: 2, expected: 1 })));
+        assert_eq!(pending_after_refusal, 0);
+        assert_eq!(accepted, Ok(()));
+        assert_eq!(pending_after_accept, 2);
+        assert!(mempool.pending().is_empty());
+    }
+
+
+    #[test]
+    fn test_refused_batch_restores_what_it_replaced_and_evicted() {
+        let mempool = init_mempool(2, 10, NonceGapPolicy::Reject);
+        mempool.submit(with_fee(1, 0, 1, "a0"), 0).unwrap();
+        mempool.submit(with_fee(2, 0, 1, "b0"), 0).unwrap();
+        let before = mempool.pending();
+
+        let refused = mempool.submit_all(
+            vec![with_fee(1, 0, 5, "a0 again"), with_fee(3, 0, 5, "c0"), signed(3, 2, "gap")],
+            &BTreeMap::new(),
+        );
+        let after_refusal = mempool.pending();
+        let dropped_after_refusal = [mempool.dropped(&signed(1, 0, "").sender), mempool.dropped(&signed(2, 0, "").sender)];
+        let fills_up = mempool.submit(with_fee(4, 0, 1, "d0"), 0);
+        mempool.submit(with_fee(1, 0, 5, "a0 again"), 0).unwrap();
+
+        assert_eq!(refused, Err((2, MempoolError::Gap { nonce: 2, expected: 1 })));
+        assert_eq!(
+            after_refusal.iter().map(|pending| (&pending.payload, pending.fee)).collect::<Vec<_>>(),
+            before.iter().map(|pending| (&pending.payload, pending.fee)).collect::<Vec<_>>()
+        );
+        assert_eq!(fills_up, Err(MempoolError::Full));
+        assert_eq!(dropped_after_refusal, [vec![], vec![]]);
+        assert_eq!(mempool.dropped(&signed(1, 0, "").sender), vec![DroppedTransaction {
+            nonce: 0,
+            reason: "replaced by a fee of 5".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_expiry_drops_the_nonces_stranded_behind_it() {
+        let mempool = Mempool::new(MempoolConfig {
+            capacity: 10,
+            per_sender: 10,
+            ttl: Duration::from_millis(200),
+            nonce_gap: NonceGapPolicy::Reject,
+        });
+
+        mempool.submit(signed(1, 0, "a0"), 0).unwrap();
+        mempool.submit(signed(2, 0, "b0"), 0).unwrap();
+        std::thread::sleep(Duration::from_millis(120));
+        mempool.submit(signed(1, 1, "a1"), 0).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+
+        assert!(mempool.pending().is_empty());
+        assert_eq!(mempool.submit(signed(1, 1, "a1"), 0), Err(MempoolError::Gap { nonce: 1, expected: 0 }));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::db::store::Durability;
+use crate::repository::{Repository, RepositoryError};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Semaphore;
+
+/// Async facade over `Repository` for the API handlers. Every call runs on
+/// tokio's blocking pool so LevelDB reads, writes and fsyncs never stall a
+/// runtime worker. At most `max_concurrent` calls run at once; a call that
+/// cannot start within `queue_timeout` fails with `RepositoryError::Busy`
+/// instead of queueing without bound.
+#[derive(Clone)]
+pub struct AsyncRepository {
+    repository: Arc<Repository>,
+    permits: Arc<Semaphore>,
+    queue_timeout: Duration,
+}
+
+impl AsyncRepository {
+    pub fn new(repository: Arc<Repository>, max_concurrent: usize, queue_timeout: Duration) -> Self {
+        AsyncRepository {
+            repository,
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            queue_timeout,
+        }
+    }
+
+
+    /// Runs `work` against the repository on the blocking pool. Once started,
+    /// `work` keeps its slot until it finishes, even if the caller gives up.
+    pub async fn run<T, F>(&self, work: F) -> Result<T, RepositoryError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Repository) -> Result<T, RepositoryError> + Send + 'static,
+    {
+        let permit = match tokio::time::timeout(self.queue_timeout, Arc::clone(&self.permits).acquire_owned()).await {
+            Ok(Ok(permit)) => permit,
+            Ok(Err(_)) | Err(_) => return Err(RepositoryError::Busy),
+        };
+
+        let repository = Arc::clone(&self.repository);
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            work(&repository)
+        })
+        .await
+        .map_err(|e| RepositoryError::Worker(e.to_string()))?
+    }
+
+
+    /// Guarantee that applies once a write returns `Ok`. Never touches disk,
+    /// so it is safe to call from async code.
+    pub fn durability(&self) -> Durability {
+        self.repository.durability()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::db::memory::MemoryStore;
+    use crate::repository::{Repository, RepositoryError};
+    use crate::repository::blocking::AsyncRepository;
+    use crate::transaction::testing::transaction;
+
+    use std::sync::Arc;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_full_pool_reports_busy() {
+        let repository = Arc::new(Repository::new(MemoryStore::new()));
+        let async_repository = AsyncRepository::new(Arc::clone(&repository), 1, Duration::from_millis(20));
+
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let holder = async_repository.clone();
+        let held = tokio::spawn(async move {
+            holder.run(move |repository| {
+                started_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+                repository.insert_transaction(&transaction("held"))
+            }).await
+        });
+        tokio::task::spawn_blocking(move || started_rx.recv().unwrap()).await.unwrap();
+
+        let busy = async_repository.run(|repository| repository.read_transaction(&1)).await;
+        release_tx.send(()).unwrap();
+        let inserted = held.await.unwrap();
+        let read = async_repository.run(|repository| repository.read_transaction(&1)).await;
+
+        assert_eq!(busy, Err(RepositoryError::Busy));
+        assert_eq!(inserted, Ok(1));
+        assert_eq!(read.map(|stored| stored.transaction.payload), Ok("held".to_string()));
+    }
+}
@@ -0,0 +1,229 @@
+use crate::db::DbError;
+use crate::db::keys::{decode_u64, DbKey, Keyspace};
+use crate::ledger::{Block, BlockId, Hash};
+use crate::repository::{Repository, RepositoryError};
+use crate::repository::state::KvStateMachine;
+use crate::state::{Receipt, StateMachine};
+use crate::transaction::Transaction;
+
+/// Name of the meta record holding the height of the latest block, stored
+/// as a big-endian u64.
+const CHAIN_HEAD: &str = "chain_head";
+
+/// What `append_block` did with the transactions it was given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppendedBlock {
+    /// `None` when every transaction was left out.
+    pub block: Option<Block>,
+    /// Transactions left out, with the state machine's reason.
+    pub dropped: Vec<(Transaction, RepositoryError)>,
+}
+
+impl Repository {
+    /// Applies `transactions` in order and commits them together with the
+    /// block that orders them, in one atomic write batch. Transactions the
+    /// state machine refuses, such as ones whose nonce does not follow on
+    /// from their sender's, are left out and returned as dropped.
+    pub fn append_block(&self, transactions: &[Transaction], timestamp: u64) -> Result<AppendedBlock, RepositoryError> {
+        let mut state = self.begin()?;
+        let mut receipts = Vec::with_capacity(transactions.len());
+        let mut dropped = Vec::new();
+        for transaction in transactions {
+            match state.apply(transaction) {
+                Ok(receipt) => receipts.push(receipt),
+                Err(e @ (RepositoryError::StaleNonce { .. }
+                    | RepositoryError::NonceGap { .. }
+                    | RepositoryError::InvalidSender(_))) => dropped.push((transaction.clone(), e)),
+                Err(e) => return Err(e),
+            }
+        }
+        if receipts.is_empty() {
+            return Ok(AppendedBlock { block: None, dropped });
+        }
+
+        let block = self.commit_block(&mut state, receipts, timestamp)?;
+        Ok(AppendedBlock { block: Some(block), dropped })
+    }
+
+
+    /// Commits the pending changes of `state` together with the next block,
+    /// which orders `receipts`, in one atomic write batch. Every change to
+    /// the state goes through here, so each new state root is a block's.
+    pub(super) fn commit_block(
+        &self,
+        state: &mut KvStateMachine<'_>,
+        receipts: Vec<Receipt>,
+        timestamp: u64,
+    ) -> Result<Block, RepositoryError> {
+        let parent = match self.load_chain_head()? {
+            Some(height) => Some(self.read_block(height)?),
+            None => None,
+        };
+        let (hashes, keys) = receipts.into_iter().map(|receipt| (receipt.hash, receipt.key)).unzip();
+        let block = Block::new(parent.as_ref(), timestamp, hashes, keys, state.root());
+        let value = serde_json::to_vec(&block)
+            .map_err(|e| RepositoryError::Encode(e.to_string()))?;
+        state.stage(block_key(block.height), value);
+        state.stage(block_hash_key(&block.hash), block.height.to_be_bytes().to_vec());
+        state.stage(chain_head_key(), block.height.to_be_bytes().to_vec());
+        state.commit()?;
+
+        Ok(block)
+    }
+
+
+    /// Whether any block was produced yet. Once one was, the state may only
+    /// change through further blocks.
+    pub(super) fn has_blocks(&self) -> Result<bool, RepositoryError> {
+        Ok(self.load_chain_head()?.is_some())
+    }
+
+
+    pub fn find_block(&self, id: &BlockId) -> Result<Block, RepositoryError> {
+        let height = match id {
+            BlockId::Latest => self.load_chain_head()?,
+            BlockId::Height(height) => Some(*height),
+            BlockId::Hash(hash) => match self.store.get(&block_hash_key(hash))? {
+                None => None,
+                Some(bytes) => Some(decode_u64(&bytes).ok_or_else(|| RepositoryError::CorruptBlock {
+                    height: 0,
+                    reason: format!("hash index for {} is not 8 bytes", hash),
+                })?),
+            },
+        };
+
+        match height {
+            Some(height) => self.read_block(height).map_err(|e| match e {
+                RepositoryError::BlockNotFound(_) => RepositoryError::BlockNotFound(id.to_string()),
+                e => e,
+            }),
+            None => Err(RepositoryError::BlockNotFound(id.to_string())),
+        }
+    }
+
+
+    fn read_block(&self, height: u64) -> Result<Block, RepositoryError> {
+        let bytes = match self.store.read(&block_key(height)) {
+            Err(DbError::NotFound(_)) => return Err(RepositoryError::BlockNotFound(height.to_string())),
+            result => result?,
+        };
+        let block: Block = serde_json::from_slice(&bytes)
+            .map_err(|e| RepositoryError::CorruptBlock { height, reason: e.to_string() })?;
+        if block.height != height || block.header_hash() != block.hash {
+            return Err(RepositoryError::CorruptBlock { height, reason: "header does not match its hash".to_string() });
+        }
+
+        Ok(block)
+    }
+
+
+    fn load_chain_head(&self) -> Result<Option<u64>, RepositoryError> {
+        match self.store.get(&chain_head_key())? {
+            None => Ok(None),
+            Some(bytes) => decode_u64(&bytes).map(Some).ok_or_else(|| RepositoryError::CorruptBlock {
+                height: 0,
+                reason: "stored chain head is not 8 bytes".to_string(),
+            }),
+        }
+    }
+}
+
+
+/// Blocks by height sort in height order under `blk/h`; the hash index
+/// lives under `blk/x`.
+fn block_key(height: u64) -> DbKey {
+    DbKey::new(Keyspace::Block).push("h").push(height)
+}
+
+
+fn block_hash_key(hash: &Hash) -> DbKey {
+    DbKey::new(Keyspace::Block).push("x").push(hash.0)
+}
+
+
+fn chain_head_key() -> DbKey {
+    DbKey::new(Keyspace::Meta).push(CHAIN_HEAD)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::db::memory::MemoryStore;
+    use crate::ledger::{BlockId, Hash};
+    use crate::repository::{Repository, RepositoryError};
+    use crate::transaction::testing::signed;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_blocks_chain_and_are_found_by_height_hash_and_latest() {
+        let store = Arc::new(MemoryStore::new());
+        let repository = Repository::new(Arc::clone(&store));
+
+        let none_yet = repository.find_block(&BlockId::Latest);
+        let first = repository.append_block(&[signed(1, 0, "a"), signed(2, 0, "b")], 1_000).unwrap().block.unwrap();
+        drop(repository);
+        let reopened = Repository::new(store);
+        let second = reopened.append_block(&[signed(1, 1, "c")], 2_000).unwrap().block.unwrap();
+
+        assert_eq!(none_yet, Err(RepositoryError::BlockNotFound("latest".to_string())));
+        assert_eq!(second.height, 1);
+        assert_eq!(second.parent_hash, first.hash);
+        assert_eq!(second.keys, vec![3]);
+        assert_eq!(reopened.find_block(&BlockId::Latest), Ok(second.clone()));
+        assert_eq!(reopened.find_block(&BlockId::Height(0)), Ok(first.clone()));
+        assert_eq!(reopened.find_block(&BlockId::Hash(first.hash)), Ok(first));
+        assert_eq!(reopened.find_block(&BlockId::Height(2)), Err(RepositoryError::BlockNotFound("2".to_string())));
+        assert!(matches!(reopened.find_block(&BlockId::Hash(Hash::ZERO)), Err(RepositoryError::BlockNotFound(_))));
+        assert_eq!(reopened.read_transaction(&3).map(|stored| stored.transaction), Ok(signed(1, 1, "c")));
+    }
+
+    #[test]
+    fn test_out_of_line_nonces_are_left_out() {
+        let repository = Repository::new(MemoryStore::new());
+        repository.insert_transaction(&signed(1, 0, "already stored")).unwrap();
+
+        let block = repository.append_block(&[signed(1, 0, "replay"), signed(1, 2, "gap"), signed(2, 0, "fine")], 1_000);
+        let empty = repository.append_block(&[signed(1, 0, "replay")], 2_000);
+
+        let appended = block.unwrap();
+        let block = appended.block.unwrap();
+        assert_eq!(block.transactions, vec![signed(2, 0, "fine").hash().unwrap()]);
+        assert_eq!(block.keys, vec![2]);
+        assert_eq!(appended.dropped.len(), 2);
+        assert_eq!(appended.dropped[0].0, signed(1, 0, "replay"));
+        assert!(matches!(appended.dropped[0].1, RepositoryError::StaleNonce { nonce: 0, expected: 1, .. }));
+        assert!(matches!(appended.dropped[1].1, RepositoryError::NonceGap { nonce: 2, expected: 1, .. }));
+        let empty = empty.unwrap();
+        assert_eq!(empty.block, None);
+        assert_eq!(empty.dropped.len(), 1);
+        assert_eq!(repository.next_nonce(&signed(1, 0, "").sender), Ok(1));
+    }
+
+    #[test]
+    fn test_state_root_ignores_how_transactions_were_blocked() {
+        let one_block = Repository::new(MemoryStore::new());
+        let two_blocks = Repository::new(MemoryStore::new());
+
+        let whole = one_block.append_block(&[signed(1, 0, "a"), signed(2, 0, "b")], 1_000).unwrap().block.unwrap();
+        let first = two_blocks.append_block(&[signed(1, 0, "a")], 5_000).unwrap().block.unwrap();
+        let second = two_blocks.append_block(&[signed(2, 0, "b")], 6_000).unwrap().block.unwrap();
+
+        assert_ne!(first.state_root, second.state_root);
+        assert_eq!(second.state_root, whole.state_root);
+        assert_ne!(second.hash, whole.hash);
+        assert_eq!(one_block.begin().unwrap().root(), whole.state_root);
+    }
+
+    #[test]
+    fn test_corrupt_blocks_are_reported() {
+        let repository = Repository::new(MemoryStore::new());
+        let block = repository.append_block(&[signed(1, 0, "a")], 1_000).unwrap().block.unwrap();
+
+        let mut tampered = block.clone();
+        tampered.timestamp += 1;
+        repository.store.put(&super::block_key(0), &serde_json::to_vec(&tampered).unwrap()).unwrap();
+
+        assert!(matches!(repository.find_block(&BlockId::Latest), Err(RepositoryError::CorruptBlock { height: 0, .. })));
+        assert!(matches!(repository.find_block(&BlockId::Hash(block.hash)), Err(RepositoryError::CorruptBlock { .. })));
+    }
+}
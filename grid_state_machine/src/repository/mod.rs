@@ -1,76 +1,538 @@
-use crate::db::DatabaseState;
+pub mod blocking;
+pub mod blocks;
+pub mod state;
+
+use crate::db::DbError;
+use crate::db::store::{BatchOp, Direction, Durability, KvStore, WriteBatch};
+use crate::db::keys::{decode_i32, decode_u64, DbKey, Keyspace};
+use crate::repository::state::KvStateMachine;
+use crate::ledger::now_millis;
+use crate::state::{Receipt, StatePath, StateQuery, StateValue, STATE_KEYSPACES};
+use crate::state::smt::SparseMerkleTree;
+use crate::transaction::{StoredRecord, Transaction};
+
+use serde::{Serialize, Deserialize};
+
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock};
+
+/// Name of the meta record holding the last key handed out to a
+/// transaction, stored as a big-endian i32.
+const TX_COUNTER: &str = "tx_counter";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepositoryError {
+    NotFound(i32),
+    /// The record was deleted; its key stays reserved by a tombstone.
+    Deleted(i32),
+    /// A compare-and-swap write saw a different version than the caller expected.
+    VersionMismatch { key: i32, expected: u64, actual: u64 },
+    /// The stored bytes under `key` do not decode as a `Transaction`.
+    Corrupt { key: i32, reason: String },
+    KeyCounter(String),
+    Encode(String),
+    Db(DbError),
+    /// Every storage slot stayed taken for the whole queue timeout.
+    Busy,
+    /// A storage task panicked or was cancelled.
+    Worker(String),
+    /// `sender` is not a hex-encoded public key.
+    InvalidSender(String),
+    /// The nonce was already used by `sender`.
+    StaleNonce { sender: String, nonce: u64, expected: u64 },
+    /// The nonce skips ahead of `expected`.
+    NonceGap { sender: String, nonce: u64, expected: u64 },
+    /// An update was signed by someone other than the record's sender.
+    SenderMismatch(i32),
+    /// The record under the key predates signed transactions and has no
+    /// sender to sign a change to it.
+    Unsigned(i32),
+    /// A direct write to the key was refused because it would change the
+    /// state root outside a block.
+    OutsideLedger(i32),
+    /// No block has this height or hash, or no block was produced yet.
+    BlockNotFound(String),
+    /// The stored block at `height` does not decode or match its hash.
+    CorruptBlock { height: u64, reason: String },
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepositoryError::NotFound(key) => write!(f, "Repository: Key {} not found", key),
+            RepositoryError::Deleted(key) => write!(f, "Repository: Key {} was deleted", key),
+            RepositoryError::VersionMismatch { key, expected, actual } => write!(
+                f, "Repository: Key {} is at version {}, expected {}", key, actual, expected
+            ),
+            RepositoryError::Corrupt { key, reason } => {
+                write!(f, "Repository: Record {} is corrupt: {}", key, reason)
+            }
+            RepositoryError::KeyCounter(reason) => write!(f, "Repository: Key counter: {}", reason),
+            RepositoryError::Encode(reason) => write!(f, "Repository: Failed to encode: {}", reason),
+            RepositoryError::Db(e) => write!(f, "Repository: {}", e),
+            RepositoryError::Busy => write!(f, "Repository: Storage is busy"),
+            RepositoryError::Worker(reason) => write!(f, "Repository: Storage task failed: {}", reason),
+            RepositoryError::InvalidSender(reason) => write!(f, "Repository: Invalid sender: {}", reason),
+            RepositoryError::StaleNonce { sender, nonce, expected } => write!(
+                f, "Repository: Nonce {} of {} is already used, next is {}", nonce, sender, expected
+            ),
+            RepositoryError::NonceGap { sender, nonce, expected } => write!(
+                f, "Repository: Nonce {} of {} skips ahead of {}", nonce, sender, expected
+            ),
+            RepositoryError::SenderMismatch(key) => {
+                write!(f, "Repository: Key {} belongs to another sender", key)
+            }
+            RepositoryError::Unsigned(key) => write!(f, "Repository: Key {} holds an unsigned legacy record", key),
+            RepositoryError::OutsideLedger(key) => {
+                write!(f, "Repository: Key {} is committed by the ledger and cannot be written directly", key)
+            }
+            RepositoryError::BlockNotFound(id) => write!(f, "Repository: Block {} not found", id),
+            RepositoryError::CorruptBlock { height, reason } => {
+                write!(f, "Repository: Block {} is corrupt: {}", height, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<DbError> for RepositoryError {
+    fn from(e: DbError) -> Self {
+        RepositoryError::Db(e)
+    }
+}
+
+/// A live transaction together with its record version, which callers pass
+/// back to `update_transaction` and `delete_transaction`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionedTransaction {
+    pub version: u64,
+    #[serde(flatten)]
+    pub transaction: Transaction,
+}
+
+/// An unsigned record from before signed transactions, read back in its
+/// original `{"version":..,"data":..}` shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LegacyRecord {
+    pub version: u64,
+    pub data: String,
+}
+
+/// One page of `list_transactions`. `next` is the cursor for the following
+/// page, or `None` once the listing is exhausted. `skipped` holds the
+/// corrupt records the page passed over, so one bad record cannot stop a
+/// listing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionPage {
+    pub transactions: Vec<(i32, VersionedTransaction)>,
+    pub skipped: Vec<CorruptRecord>,
+    pub next: Option<i32>,
+}
+
+/// A stored record that failed to decode, as reported by `scan_corrupt`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CorruptRecord {
+    pub key: i32,
+    pub reason: String,
+}
 
 pub struct Repository {
-    db: DatabaseState,
+    store: Box<dyn KvStore>,
+    /// Last key handed out. Every write holds this lock, so the existence,
+    /// version and nonce checks of a write cannot race another writer.
+    last_key: Mutex<Option<i32>>,
+    /// Sparse Merkle tree over the committed state, rebuilt from the store on
+    /// open. Writers replace it while holding the write lock across the
+    /// store write, so a reader holding the read lock sees records and tree
+    /// at the same commit. The tree is only ever swapped whole, so a
+    /// poisoned lock still guards a consistent tree and is read through.
+    tree: RwLock<SparseMerkleTree>,
 }
 
 impl Repository {
-    pub fn new(db: DatabaseState) -> Self {
-        Repository { db }
+    pub fn new(store: impl KvStore + 'static) -> Self {
+        let tree = STATE_KEYSPACES.iter()
+            .flat_map(|keyspace| store.keyspace_entries(*keyspace))
+            .map(|(key, value)| (key.as_bytes().to_vec(), value))
+            .collect();
+        Repository { store: Box::new(store), last_key: Mutex::new(None), tree: RwLock::new(tree) }
     }
 
 
-    pub fn add_transaction(&self, key: &i32, value: Vec<u8>) -> Result<(), String> {
-        let slice_ref: &[u8] = value.as_slice();
-        
-        match DatabaseState::insert_key(&self.db, &key, slice_ref) {
-            Ok(()) => {
-                Ok(())
+    /// Guarantee that applies once a write method returns `Ok`.
+    pub fn durability(&self) -> Durability {
+        self.store.durability()
+    }
+
+
+    /// Writes raw bytes under `key`, bypassing the counter, decoding and the
+    /// ledger. Tests use it to plant colliding or corrupt records. Refused
+    /// once a block exists, since it would change the state root outside one.
+    #[cfg(test)]
+    pub fn add_transaction(&self, key: &i32, value: Vec<u8>) -> Result<(), RepositoryError> {
+        let _writer = self.lock_writer()?;
+        if self.has_blocks()? {
+            return Err(RepositoryError::OutsideLedger(*key));
+        }
+        let mut tree = self.tree.write().unwrap_or_else(PoisonError::into_inner);
+        self.store.put(&transaction_key(*key), value.as_slice())?;
+        tree.insert(transaction_key(*key).as_bytes(), &value);
+        Ok(())
+    }
+
+
+    pub fn get_transaction(&self, key: &i32) -> Result<Vec<u8>, RepositoryError> {
+        match self.store.read(&transaction_key(*key)) {
+            Err(DbError::NotFound(_)) => Err(RepositoryError::NotFound(*key)),
+            result => Ok(result?),
+        }
+    }
+
+
+    /// Starts a state machine session over the repository. It holds the
+    /// writer lock until it is dropped, so keep it short.
+    pub fn begin(&self) -> Result<KvStateMachine<'_>, RepositoryError> {
+        KvStateMachine::new(self)
+    }
+
+
+    /// Stores `transaction` under the next free key, in a block of its own,
+    /// and returns that key. Tests use it to set up state without a mempool;
+    /// unlike `append_block`, a refused transaction fails the call.
+    #[cfg(test)]
+    pub fn insert_transaction(&self, transaction: &Transaction) -> Result<i32, RepositoryError> {
+        let appended = self.append_block(std::slice::from_ref(transaction), now_millis())?;
+        if let Some((_, e)) = appended.dropped.into_iter().next() {
+            return Err(e);
+        }
+        Ok(appended.block.expect("a stored transaction makes a block").keys[0])
+    }
+
+
+    /// Nonce the next stored transaction of the hex-encoded `sender` must carry.
+    pub fn next_nonce(&self, sender: &str) -> Result<u64, RepositoryError> {
+        let sender = hex::decode(sender)
+            .map_err(|e| RepositoryError::InvalidSender(e.to_string()))?;
+        self.load_nonce(&sender)
+    }
+
+
+    /// The signed transaction under `key`. Legacy records are read through
+    /// `query` instead.
+    pub fn read_transaction(&self, key: &i32) -> Result<VersionedTransaction, RepositoryError> {
+        match self.read_record(key)? {
+            StoredRecord::Live { version, transaction } => Ok(VersionedTransaction { version, transaction }),
+            StoredRecord::Legacy { .. } => Err(RepositoryError::Unsigned(*key)),
+            StoredRecord::Tombstone { .. } => Err(RepositoryError::Deleted(*key)),
+        }
+    }
+
+
+    /// Replaces the transaction under `key` if it is still at
+    /// `expected_version`, and returns the new version. See
+    /// `KvStateMachine::update`.
+    pub fn update_transaction(
+        &self,
+        key: &i32,
+        expected_version: u64,
+        transaction: &Transaction,
+    ) -> Result<u64, RepositoryError> {
+        let mut state = self.begin()?;
+        let version = state.update(key, expected_version, transaction)?;
+        self.commit_block(&mut state, vec![receipt(*key, transaction)?], now_millis())?;
+        Ok(version)
+    }
+
+
+    /// Replaces the transaction under `key` with a tombstone if it is still
+    /// at `expected_version`, and returns the tombstone's version. See
+    /// `KvStateMachine::delete`.
+    pub fn delete_transaction(
+        &self,
+        key: &i32,
+        expected_version: u64,
+        transaction: &Transaction,
+    ) -> Result<u64, RepositoryError> {
+        let mut state = self.begin()?;
+        let version = state.delete(key, expected_version, transaction)?;
+        self.commit_block(&mut state, vec![receipt(*key, transaction)?], now_millis())?;
+        Ok(version)
+    }
+
+
+    fn read_record(&self, key: &i32) -> Result<StoredRecord, RepositoryError> {
+        decode_record(*key, &self.get_transaction(key)?)
+    }
+
+
+    /// Lists up to `limit` live transactions in key order, starting just past
+    /// `after` (or at the first key in `direction` when `None`). Tombstones
+    /// and unsigned legacy records, which only `query` reads, are skipped.
+    /// Corrupt records are skipped too and reported in the page.
+    pub fn list_transactions(
+        &self,
+        after: Option<i32>,
+        limit: usize,
+        direction: Direction,
+    ) -> Result<TransactionPage, RepositoryError> {
+        let after = after.map(transaction_key);
+        let mut transactions = Vec::with_capacity(limit);
+        let mut skipped = Vec::new();
+        let mut more = false;
+
+        for (key, bytes) in self.store.scan(Keyspace::Transaction, after.as_ref(), direction) {
+            let Some(key) = key.suffix(Keyspace::Transaction).and_then(decode_i32) else {
+                continue;
+            };
+            let record = match StoredRecord::decode(&bytes) {
+                Ok(StoredRecord::Live { version, transaction }) => Ok(VersionedTransaction { version, transaction }),
+                Ok(StoredRecord::Legacy { .. } | StoredRecord::Tombstone { .. }) => continue,
+                Err(e) => Err(CorruptRecord { key, reason: e.to_string() }),
+            };
+            if transactions.len() == limit {
+                more = true;
+                break;
             }
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                Err("Repository: Failed to add to db.".to_string())
+            match record {
+                Ok(transaction) => transactions.push((key, transaction)),
+                Err(corrupt) => skipped.push(corrupt),
             }
         }
+
+        // The cursor is the last key this page covered, listed or skipped.
+        let last = transactions.last().map(|(key, _)| *key).into_iter()
+            .chain(skipped.last().map(|record| record.key))
+            .reduce(|a, b| match direction {
+                Direction::Forward => a.max(b),
+                Direction::Reverse => a.min(b),
+            });
+        let next = if more { last } else { None };
+
+        Ok(TransactionPage { transactions, skipped, next })
+    }
+
+
+    /// Lists every stored record that does not decode as a transaction or tombstone.
+    pub fn scan_corrupt(&self) -> Vec<CorruptRecord> {
+        self.store.keyspace_entries(Keyspace::Transaction)
+            .filter_map(|(key, bytes)| {
+                let key = key.suffix(Keyspace::Transaction).and_then(decode_i32)?;
+                match StoredRecord::decode(&bytes) {
+                    Ok(_) => None,
+                    Err(e) => Some(CorruptRecord { key, reason: e.to_string() }),
+                }
+            })
+            .collect()
+    }
+
+
+    /// Deletes the record under `key` outright, for `scan --repair`. Refused
+    /// once a block exists: the record is then part of a committed state
+    /// root, which only a block may change.
+    pub fn remove_transaction(&self, key: &i32) -> Result<(), RepositoryError> {
+        let _writer = self.lock_writer()?;
+        if self.has_blocks()? {
+            return Err(RepositoryError::OutsideLedger(*key));
+        }
+        let mut tree = self.tree.write().unwrap_or_else(PoisonError::into_inner);
+        self.store.delete(&transaction_key(*key))?;
+        tree.remove(transaction_key(*key).as_bytes());
+        Ok(())
     }
 
 
-    pub fn get_transaction(&self, key: &i32) -> Result<Vec<u8>, String> {
-        match DatabaseState::read_key(&self.db, &key) {
-            Ok(result) => {
-                Ok(result)
+    /// Moves records written before keys were split into keyspaces, when
+    /// every key was a bare big-endian i32, into the transaction keyspace.
+    /// Legacy key 0 held the key counter. The move is one atomic batch.
+    /// Returns the number of records moved.
+    pub fn migrate_legacy_keys(&self) -> Result<usize, RepositoryError> {
+        let mut last_key = self.lock_writer()?;
+
+        let snapshot = self.store.snapshot();
+        let legacy: Vec<(DbKey, i32, Vec<u8>)> = snapshot.iter_from(&DbKey::default())
+            .filter(|(key, _)| key.keyspace().is_none())
+            .filter_map(|(key, value)| {
+                let legacy_key = i32::from_be_bytes(key.as_bytes().try_into().ok()?);
+                Some((key, legacy_key, value))
+            })
+            .collect();
+
+        let mut counter = self.load_key_counter()?;
+        let mut batch = WriteBatch::new();
+        for (key, legacy_key, value) in &legacy {
+            if *legacy_key == 0 {
+                let stored: [u8; 4] = value.as_slice().try_into()
+                    .map_err(|_| RepositoryError::KeyCounter("stored value is not 4 bytes".to_string()))?;
+                counter = counter.max(i32::from_be_bytes(stored));
+            } else {
+                if snapshot.get(&transaction_key(*legacy_key))?.is_none() {
+                    batch.put(transaction_key(*legacy_key), value.clone());
+                }
+                counter = counter.max(*legacy_key);
             }
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                Err("Repository: Key not found.".to_string())
+            batch.delete(key.clone());
+        }
+
+        if !legacy.is_empty() {
+            batch.put(counter_key(), counter.to_be_bytes());
+            let mut tree = self.tree.write().unwrap_or_else(PoisonError::into_inner);
+            self.store.write_batch(&batch)?;
+            for op in batch.ops() {
+                if let BatchOp::Put(key, value) = op {
+                    if key.keyspace() == Some(Keyspace::Transaction) {
+                        tree.insert(key.as_bytes(), value);
+                    }
+                }
+            }
+            *last_key = Some(counter);
+        }
+
+        Ok(legacy.len())
+    }
+
+
+    fn lock_writer(&self) -> Result<MutexGuard<'_, Option<i32>>, RepositoryError> {
+        self.last_key.lock()
+            .map_err(|_| RepositoryError::KeyCounter("lock poisoned".to_string()))
+    }
+
+
+    fn load_nonce(&self, sender: &[u8]) -> Result<u64, RepositoryError> {
+        match self.store.get(&nonce_key(sender))? {
+            None => Ok(0),
+            Some(bytes) => decode_nonce(&bytes),
+        }
+    }
+
+
+    fn load_key_counter(&self) -> Result<i32, RepositoryError> {
+        match self.store.get(&counter_key())? {
+            None => Ok(0),
+            Some(bytes) => {
+                let bytes: [u8; 4] = bytes.as_slice().try_into()
+                    .map_err(|_| RepositoryError::KeyCounter("stored value is not 4 bytes".to_string()))?;
+                Ok(i32::from_be_bytes(bytes))
             }
         }
     }
 }
 
 
+impl StateQuery for Repository {
+    /// Reads committed state only.
+    fn query(&self, path: &StatePath) -> Result<StateValue, RepositoryError> {
+        match path {
+            StatePath::Transaction(key) => match self.read_transaction(key) {
+                Err(RepositoryError::Unsigned(_)) => record_value(*key, self.read_record(key)?),
+                result => result.map(StateValue::Transaction),
+            },
+            StatePath::Nonce(sender) => self.next_nonce(sender).map(StateValue::Nonce),
+        }
+    }
+}
+
+
+fn transaction_key(key: i32) -> DbKey {
+    DbKey::new(Keyspace::Transaction).push(key)
+}
+
+
+fn counter_key() -> DbKey {
+    DbKey::new(Keyspace::Meta).push(TX_COUNTER)
+}
+
+
+/// Account keys start with the sender's protobuf key, length-prefixed so
+/// one sender's keys never run into another's.
+fn nonce_key(sender: &[u8]) -> DbKey {
+    DbKey::new(Keyspace::Account).push(sender.len() as u32).push(sender).push("nonce")
+}
+
+
+fn decode_record(key: i32, bytes: &[u8]) -> Result<StoredRecord, RepositoryError> {
+    StoredRecord::decode(bytes).map_err(|e| RepositoryError::Corrupt { key, reason: e.to_string() })
+}
+
+
+/// What a read of the record under `key` returns.
+fn record_value(key: i32, record: StoredRecord) -> Result<StateValue, RepositoryError> {
+    match record {
+        StoredRecord::Live { version, transaction } => {
+            Ok(StateValue::Transaction(VersionedTransaction { version, transaction }))
+        }
+        StoredRecord::Legacy { version, data } => Ok(StateValue::Legacy(LegacyRecord { version, data })),
+        StoredRecord::Tombstone { .. } => Err(RepositoryError::Deleted(key)),
+    }
+}
+
+
+/// Names the signed `transaction` that changed the record under `key` in
+/// the block that commits the change.
+fn receipt(key: i32, transaction: &Transaction) -> Result<Receipt, RepositoryError> {
+    let hash = transaction.hash()
+        .map_err(|e| RepositoryError::Encode(e.to_string()))?;
+    Ok(Receipt { key, hash })
+}
+
+
+fn decode_nonce(bytes: &[u8]) -> Result<u64, RepositoryError> {
+    decode_u64(bytes).ok_or_else(|| RepositoryError::KeyCounter("stored nonce is not 8 bytes".to_string()))
+}
+
+
+fn sender_bytes(transaction: &Transaction) -> Result<Vec<u8>, RepositoryError> {
+    hex::decode(&transaction.sender).map_err(|e| RepositoryError::InvalidSender(e.to_string()))
+}
+
+
+fn check_nonce(transaction: &Transaction, expected: u64) -> Result<(), RepositoryError> {
+    let (sender, nonce) = (transaction.sender.clone(), transaction.nonce);
+    if nonce < expected {
+        Err(RepositoryError::StaleNonce { sender, nonce, expected })
+    } else if nonce > expected {
+        Err(RepositoryError::NonceGap { sender, nonce, expected })
+    } else {
+        Ok(())
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
-    use crate::db::DatabaseState;
-    use crate::repository::Repository;
+    use crate::db::keys::DbKey;
+    use crate::db::memory::MemoryStore;
+    use db_key::Key;
+    use crate::db::store::Direction;
+    use crate::repository::{
+        CorruptRecord, LegacyRecord, Repository, RepositoryError, TransactionPage, VersionedTransaction,
+    };
+    use crate::state::{StatePath, StateQuery, StateValue};
+    use crate::transaction::StoredRecord;
+    use crate::transaction::testing::{signed, transaction};
+    use std::sync::Arc;
 
-    fn init_repository(db_path: String) -> (Repository, String) {
-        let db_state: DatabaseState = DatabaseState::init(db_path.clone());
-        let repository = Repository::new(db_state);
-        (repository, db_path)
+    fn init_repository() -> Repository {
+        Repository::new(MemoryStore::new())
     }
 
     #[test]
     fn test_add_transaction() {
-        let (repository, db_path) = init_repository("./test_db_repository_add_tx".to_string());
+        let repository = init_repository();
         
         let key = 100;
         let value_raw = b"text_value";
         let value_vec = value_raw.to_vec();
 
-        let add_transaction_result = repository.add_transaction(&key, value_vec).unwrap();
-        
-        drop(repository);
-        std::fs::remove_dir_all(db_path)
-            .expect("Failed to remove db directory.");
-        
-        assert_eq!(add_transaction_result, ());
+        repository.add_transaction(&key, value_vec).unwrap();
+
+        assert_eq!(repository.get_transaction(&key), Ok(value_raw.to_vec()));
     }
 
 
     #[test]
     fn test_get_transaction() {
-        let (repository, db_path) = init_repository("./test_db_repository_get_tx".to_string());
+        let repository = init_repository();
 
         let key = 369;
         let value_raw = b"three-six-nine";
@@ -79,10 +541,221 @@ mod tests {
         let _add_transaction_result = repository.add_transaction(&key, value_vec.clone());
         let get_transaction_result = repository.get_transaction(&key).unwrap();
     
+        assert_eq!(get_transaction_result, value_vec);
+    }
+
+
+    #[test]
+    fn test_insert_transaction_assigns_increasing_keys() {
+        let store = Arc::new(MemoryStore::new());
+        let repository = Repository::new(Arc::clone(&store));
+
+        let first = repository.insert_transaction(&signed(1, 0, "first"));
+        let second = repository.insert_transaction(&signed(1, 1, "second"));
+        let second_stored = repository.read_transaction(&2);
+
         drop(repository);
-        std::fs::remove_dir_all(db_path)
-            .expect("Failed to remove db directory.");
+        let reopened = Repository::new(store);
+        let after_reopen = reopened.insert_transaction(&signed(1, 2, "third"));
 
-        assert_eq!(get_transaction_result, value_vec);
+        assert_eq!(first, Ok(1));
+        assert_eq!(second, Ok(2));
+        assert_eq!(second_stored.map(|stored| stored.transaction), Ok(signed(1, 1, "second")));
+        assert_eq!(after_reopen, Ok(3));
+    }
+
+
+    #[test]
+    fn test_insert_transaction_skips_existing_key() {
+        let repository = init_repository();
+
+        repository.add_transaction(&1, b"written directly".to_vec()).unwrap();
+        let inserted = repository.insert_transaction(&transaction("new"));
+        let original = repository.get_transaction(&1);
+        let counter = repository.get_transaction(&0);
+
+        assert_eq!(inserted, Ok(2));
+        assert_eq!(original, Ok(b"written directly".to_vec()));
+        assert_eq!(counter, Err(RepositoryError::NotFound(0)));
+    }
+
+
+    #[test]
+    fn test_corrupt_records_are_reported() {
+        let repository = init_repository();
+
+        repository.add_transaction(&1, StoredRecord::Live { version: 1, transaction: transaction("good") }.encode().unwrap())
+            .unwrap();
+        repository.add_transaction(&5, b"Hello, Meow!".to_vec()).unwrap();
+        repository.add_transaction(&6, b"Hello again".to_vec()).unwrap();
+
+        let read_result = repository.read_transaction(&5);
+        let corrupt = repository.scan_corrupt();
+        repository.remove_transaction(&5).unwrap();
+        let after_repair = repository.scan_corrupt();
+        repository.insert_transaction(&transaction("in a block")).unwrap();
+        let root = repository.begin().unwrap().root();
+        let repair_after_block = repository.remove_transaction(&6);
+        let plant_after_block = repository.add_transaction(&7, b"late".to_vec());
+
+        assert!(matches!(read_result, Err(RepositoryError::Corrupt { key: 5, .. })));
+        assert_eq!(corrupt.len(), 2);
+        assert!(matches!(corrupt[0], CorruptRecord { key: 5, .. }));
+        assert_eq!(after_repair.len(), 1);
+        assert_eq!(repair_after_block, Err(RepositoryError::OutsideLedger(6)));
+        assert_eq!(plant_after_block, Err(RepositoryError::OutsideLedger(7)));
+        assert_eq!(repository.begin().unwrap().root(), root);
+        assert_eq!(repository.scan_corrupt(), after_repair);
+    }
+
+
+    #[test]
+    fn test_legacy_records_are_read_but_never_changed() {
+        let repository = init_repository();
+        repository.add_transaction(&5, br#"{"data":"unsigned"}"#.to_vec()).unwrap();
+
+        let read = repository.query(&StatePath::Transaction(5));
+        let corrupt = repository.scan_corrupt();
+        let updated = repository.update_transaction(&5, 1, &transaction("takeover"));
+
+        assert_eq!(read, Ok(StateValue::Legacy(LegacyRecord { version: 1, data: "unsigned".to_string() })));
+        assert!(corrupt.is_empty());
+        assert_eq!(updated, Err(RepositoryError::Unsigned(5)));
+        assert_eq!(repository.read_transaction(&5), Err(RepositoryError::Unsigned(5)));
+    }
+
+
+    #[test]
+    fn test_migrate_legacy_keys() {
+        let repository = init_repository();
+
+        repository.store.put(&DbKey::from_u8(&0i32.to_be_bytes()), &4i32.to_be_bytes()).unwrap();
+        repository.store.put(&DbKey::from_u8(&9i32.to_be_bytes()), br#"{"data":"old"}"#).unwrap();
+
+        let moved = repository.migrate_legacy_keys();
+        let migrated = repository.query(&StatePath::Transaction(9));
+        let next = repository.insert_transaction(&transaction("new"));
+        let leftover = repository.store.iter_from(&DbKey::default())
+            .filter(|(key, _)| key.keyspace().is_none())
+            .count();
+
+        assert_eq!(moved, Ok(2));
+        assert_eq!(migrated, Ok(StateValue::Legacy(LegacyRecord { version: 1, data: "old".to_string() })));
+        assert_eq!(next, Ok(10));
+        assert_eq!(leftover, 0);
+    }
+
+    #[test]
+    fn test_list_transactions_pages_through_everything() {
+        let repository = init_repository();
+        for (nonce, data) in ["a", "b", "c", "d", "e"].into_iter().enumerate() {
+            repository.insert_transaction(&signed(1, nonce as u64, data)).unwrap();
+        }
+
+        let first = repository.list_transactions(None, 2, Direction::Forward).unwrap();
+        let second = repository.list_transactions(first.next, 2, Direction::Forward).unwrap();
+        let third = repository.list_transactions(second.next, 2, Direction::Forward).unwrap();
+        let newest = repository.list_transactions(None, 2, Direction::Reverse).unwrap();
+
+        let keys = |page: &TransactionPage| -> Vec<i32> {
+            page.transactions.iter().map(|(key, _)| *key).collect()
+        };
+        assert_eq!(keys(&first), vec![1, 2]);
+        assert_eq!(first.next, Some(2));
+        assert_eq!(keys(&second), vec![3, 4]);
+        assert_eq!(keys(&third), vec![5]);
+        assert_eq!(third.next, None);
+        assert_eq!(third.transactions[0].1.transaction, signed(1, 4, "e"));
+        assert_eq!(keys(&newest), vec![5, 4]);
+        assert_eq!(newest.next, Some(4));
+        assert!(first.skipped.is_empty());
+    }
+
+
+    #[test]
+    fn test_list_transactions_skips_corrupt_records() {
+        let repository = init_repository();
+        let live = |payload| StoredRecord::Live { version: 1, transaction: transaction(payload) }.encode().unwrap();
+        repository.add_transaction(&1, live("a")).unwrap();
+        repository.add_transaction(&2, b"Hello, Meow!".to_vec()).unwrap();
+        repository.add_transaction(&3, live("c")).unwrap();
+        repository.add_transaction(&4, b"Hello again".to_vec()).unwrap();
+
+        let first = repository.list_transactions(None, 1, Direction::Forward).unwrap();
+        let second = repository.list_transactions(first.next, 1, Direction::Forward).unwrap();
+        let third = repository.list_transactions(second.next, 1, Direction::Forward).unwrap();
+        let newest = repository.list_transactions(None, 1, Direction::Reverse).unwrap();
+
+        let keys = |page: &TransactionPage| -> (Vec<i32>, Vec<i32>) {
+            (page.transactions.iter().map(|(key, _)| *key).collect(), page.skipped.iter().map(|record| record.key).collect())
+        };
+        assert_eq!((keys(&first), first.next), ((vec![1], vec![]), Some(1)));
+        assert_eq!((keys(&second), second.next), ((vec![3], vec![2]), Some(3)));
+        assert_eq!((keys(&third), third.next), ((vec![], vec![4]), None));
+        assert_eq!((keys(&newest), newest.next), ((vec![3], vec![4]), Some(3)));
+    }
+
+
+    #[test]
+    fn test_update_and_delete_compare_versions() {
+        let repository = init_repository();
+        repository.insert_transaction(&signed(1, 0, "v1")).unwrap();
+        let key = 1;
+
+        let updated = repository.update_transaction(&key, 1, &signed(1, 1, "v2"));
+        let stale_update = repository.update_transaction(&key, 1, &signed(1, 2, "lost"));
+        let replayed = repository.update_transaction(&key, 2, &signed(1, 1, "v2"));
+        let other_sender = repository.update_transaction(&key, 2, &signed(2, 0, "mine"));
+        let stale_delete = repository.delete_transaction(&key, 1, &signed(1, 2, "delete"));
+        let foreign_delete = repository.delete_transaction(&key, 2, &signed(2, 0, "delete"));
+        let current = repository.read_transaction(&key);
+        let deleted = repository.delete_transaction(&key, 2, &signed(1, 2, "delete"));
+        let after_delete = repository.read_transaction(&key);
+        let update_deleted = repository.update_transaction(&key, 3, &signed(1, 3, "v4"));
+        let missing = repository.update_transaction(&99, 1, &signed(1, 3, "v1"));
+        let next = repository.insert_transaction(&signed(1, 3, "next"));
+        let listed = repository.list_transactions(None, 10, Direction::Forward).unwrap();
+
+        assert_eq!(updated, Ok(2));
+        assert_eq!(stale_update, Err(RepositoryError::VersionMismatch { key, expected: 1, actual: 2 }));
+        assert!(matches!(replayed, Err(RepositoryError::StaleNonce { nonce: 1, expected: 2, .. })));
+        assert_eq!(other_sender, Err(RepositoryError::SenderMismatch(key)));
+        assert_eq!(stale_delete, Err(RepositoryError::VersionMismatch { key, expected: 1, actual: 2 }));
+        assert_eq!(foreign_delete, Err(RepositoryError::SenderMismatch(key)));
+        assert_eq!(current, Ok(VersionedTransaction { version: 2, transaction: signed(1, 1, "v2") }));
+        assert_eq!(deleted, Ok(3));
+        assert_eq!(after_delete, Err(RepositoryError::Deleted(key)));
+        assert_eq!(update_deleted, Err(RepositoryError::Deleted(key)));
+        assert_eq!(missing, Err(RepositoryError::NotFound(99)));
+        assert_eq!(next, Ok(key + 1));
+        assert_eq!(listed.transactions.len(), 1);
+        assert!(repository.scan_corrupt().is_empty());
+    }
+
+
+    #[test]
+    fn test_nonces_refuse_replays_and_gaps() {
+        let store = Arc::new(MemoryStore::new());
+        let repository = Repository::new(Arc::clone(&store));
+
+        let first = repository.insert_transaction(&signed(1, 0, "pay 5"));
+        let replay = repository.insert_transaction(&signed(1, 0, "pay 5"));
+        let gap = repository.insert_transaction(&signed(1, 2, "pay 7"));
+        let other_sender = repository.insert_transaction(&signed(2, 0, "pay 5"));
+
+        drop(repository);
+        let reopened = Repository::new(store);
+        let replay_after_reopen = reopened.insert_transaction(&signed(1, 0, "pay 5"));
+        let next = reopened.insert_transaction(&signed(1, 1, "pay 6"));
+
+        assert_eq!(first, Ok(1));
+        assert!(matches!(replay, Err(RepositoryError::StaleNonce { nonce: 0, expected: 1, .. })));
+        assert!(matches!(gap, Err(RepositoryError::NonceGap { nonce: 2, expected: 1, .. })));
+        assert_eq!(other_sender, Ok(2));
+        assert!(matches!(replay_after_reopen, Err(RepositoryError::StaleNonce { .. })));
+        assert_eq!(next, Ok(3));
+        assert_eq!(reopened.next_nonce(&signed(1, 0, "").sender), Ok(2));
+        assert_eq!(reopened.next_nonce(&signed(3, 0, "").sender), Ok(0));
+        assert!(matches!(reopened.next_nonce("not hex"), Err(RepositoryError::InvalidSender(_))));
     }
 }
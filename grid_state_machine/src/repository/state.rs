@@ -0,0 +1,409 @@
+use crate::db::keys::DbKey;
+use crate::db::store::{Direction, WriteBatch};
+use crate::ledger::BlockId;
+use crate::repository::{
+    check_nonce, counter_key, decode_nonce, decode_record, nonce_key, record_value, sender_bytes, transaction_key,
+    Repository, RepositoryError, TransactionPage, VersionedTransaction,
+};
+use crate::state::{ProofBlock, ProvenRead, Receipt, StateMachine, StatePath, StateQuery, StateRoot, StateValue};
+use crate::state::smt::{SparseMerkleTree, StateProof};
+use crate::transaction::{StoredRecord, Transaction};
+
+use std::collections::BTreeMap;
+use std::sync::{MutexGuard, PoisonError};
+
+/// The default `StateMachine`: transactions become records in the
+/// transaction keyspace and advance their sender's nonce in the account
+/// keyspace. It holds the repository's writer lock from `Repository::begin`
+/// until it is dropped, so its reads and the commit cannot race another
+/// writer.
+pub struct KvStateMachine<'a> {
+    repository: &'a Repository,
+    last_key: MutexGuard<'a, Option<i32>>,
+    /// Last key handed out, counting pending records.
+    counter: i32,
+    /// Values written since the last commit.
+    pending: BTreeMap<DbKey, Vec<u8>>,
+    /// Records outside the state, such as the block that commits to it,
+    /// written in the same batch as the pending values.
+    staged: BTreeMap<DbKey, Vec<u8>>,
+}
+
+impl<'a> KvStateMachine<'a> {
+    pub(super) fn new(repository: &'a Repository) -> Result<Self, RepositoryError> {
+        let last_key = repository.lock_writer()?;
+        let counter = match *last_key {
+            Some(counter) => counter,
+            None => repository.load_key_counter()?,
+        };
+
+        Ok(KvStateMachine { repository, last_key, counter, pending: BTreeMap::new(), staged: BTreeMap::new() })
+    }
+
+
+    /// Replaces the transaction under `key` if it is still at
+    /// `expected_version`, and returns the new version. The replacement must
+    /// come from the same sender and carry that sender's next nonce, which
+    /// it consumes, so an update cannot be replayed.
+    pub fn update(&mut self, key: &i32, expected_version: u64, transaction: &Transaction) -> Result<u64, RepositoryError> {
+        let current = self.live_record(key, expected_version)?;
+        self.consume_nonce(key, &current, transaction)?;
+
+        self.put_record(*key, &StoredRecord::Live {
+            version: current.version + 1,
+            transaction: transaction.clone(),
+        })
+    }
+
+
+    /// Replaces the transaction under `key` with a tombstone if it is still
+    /// at `expected_version`, and returns the tombstone's version. Like an
+    /// update, `transaction` must come from the record's sender and consumes
+    /// that sender's next nonce; its payload is not stored.
+    pub fn delete(&mut self, key: &i32, expected_version: u64, transaction: &Transaction) -> Result<u64, RepositoryError> {
+        let current = self.live_record(key, expected_version)?;
+        self.consume_nonce(key, &current, transaction)?;
+
+        self.put_record(*key, &StoredRecord::Tombstone { version: current.version + 1 })
+    }
+
+
+    /// Root of the state including pending changes.
+    pub fn root(&self) -> StateRoot {
+        self.pending_tree().root()
+    }
+
+
+    /// Queues a record outside the state for the next `commit`, which writes
+    /// it atomically with the state. It does not change the root.
+    pub(super) fn stage(&mut self, key: DbKey, value: Vec<u8>) {
+        self.staged.insert(key, value);
+    }
+
+
+    /// The committed tree with the pending changes applied to a copy of it.
+    fn pending_tree(&self) -> SparseMerkleTree {
+        let mut tree = self.repository.tree.read().unwrap_or_else(PoisonError::into_inner).clone();
+        for (key, value) in &self.pending {
+            tree.insert(key.as_bytes(), value);
+        }
+        tree
+    }
+
+
+    fn get(&self, key: &DbKey) -> Result<Option<Vec<u8>>, RepositoryError> {
+        match self.pending.get(key) {
+            Some(value) => Ok(Some(value.clone())),
+            None => Ok(self.repository.store.get(key)?),
+        }
+    }
+
+
+    fn read_record(&self, key: &i32) -> Result<StoredRecord, RepositoryError> {
+        match self.get(&transaction_key(*key))? {
+            Some(bytes) => decode_record(*key, &bytes),
+            None => Err(RepositoryError::NotFound(*key)),
+        }
+    }
+
+
+    /// The live record under `key`, if it is at `expected_version`.
+    fn live_record(&self, key: &i32, expected_version: u64) -> Result<VersionedTransaction, RepositoryError> {
+        let current = match self.read_record(key)? {
+            StoredRecord::Live { version, transaction } => VersionedTransaction { version, transaction },
+            StoredRecord::Legacy { .. } => return Err(RepositoryError::Unsigned(*key)),
+            StoredRecord::Tombstone { .. } => return Err(RepositoryError::Deleted(*key)),
+        };
+        if current.version != expected_version {
+            return Err(RepositoryError::VersionMismatch { key: *key, expected: expected_version, actual: current.version });
+        }
+
+        Ok(current)
+    }
+
+
+    /// Checks that `transaction` comes from the sender of `current`, the
+    /// record under `key`, and carries that sender's next nonce, then stages
+    /// the nonce as used.
+    fn consume_nonce(
+        &mut self,
+        key: &i32,
+        current: &VersionedTransaction,
+        transaction: &Transaction,
+    ) -> Result<(), RepositoryError> {
+        let sender = sender_bytes(transaction)?;
+        if sender_bytes(&current.transaction)? != sender {
+            return Err(RepositoryError::SenderMismatch(*key));
+        }
+        let expected = self.load_nonce(&sender)?;
+        check_nonce(transaction, expected)?;
+
+        self.pending.insert(nonce_key(&sender), (expected + 1).to_be_bytes().to_vec());
+        Ok(())
+    }
+
+
+    /// Stages `record` under `key` and returns its version.
+    fn put_record(&mut self, key: i32, record: &StoredRecord) -> Result<u64, RepositoryError> {
+        let value = record.encode()
+            .map_err(|e| RepositoryError::Encode(e.to_string()))?;
+        self.pending.insert(transaction_key(key), value);
+        Ok(record.version())
+    }
+
+
+    fn load_nonce(&self, sender: &[u8]) -> Result<u64, RepositoryError> {
+        match self.get(&nonce_key(sender))? {
+            None => Ok(0),
+            Some(bytes) => decode_nonce(&bytes),
+        }
+    }
+
+
+    /// The first key past the counter with nothing stored under it. Keys
+    /// written around the counter are skipped rather than overwritten, the
+    /// same way on every node holding the same state.
+    fn next_free_key(&mut self) -> Result<i32, RepositoryError> {
+        loop {
+            self.counter = self.counter.checked_add(1)
+                .ok_or_else(|| RepositoryError::KeyCounter("key space exhausted".to_string()))?;
+            if self.get(&transaction_key(self.counter))?.is_none() {
+                return Ok(self.counter);
+            }
+        }
+    }
+}
+
+impl StateQuery for KvStateMachine<'_> {
+    fn query(&self, path: &StatePath) -> Result<StateValue, RepositoryError> {
+        match path {
+            StatePath::Transaction(key) => record_value(*key, self.read_record(key)?),
+            StatePath::Nonce(sender) => {
+                let sender = hex::decode(sender)
+                    .map_err(|e| RepositoryError::InvalidSender(e.to_string()))?;
+                self.load_nonce(&sender).map(StateValue::Nonce)
+            }
+        }
+    }
+}
+
+impl StateMachine for KvStateMachine<'_> {
+    /// Stores `transaction` as a new record under the next free key. Its
+    /// nonce must be its sender's next one.
+    fn apply(&mut self, transaction: &Transaction) -> Result<Receipt, RepositoryError> {
+        let sender = sender_bytes(transaction)?;
+        let expected = self.load_nonce(&sender)?;
+        check_nonce(transaction, expected)?;
+        let hash = transaction.hash()
+            .map_err(|e| RepositoryError::Encode(e.to_string()))?;
+
+        let key = self.next_free_key()?;
+        self.put_record(key, &StoredRecord::Live { version: 1, transaction: transaction.clone() })?;
+        self.pending.insert(nonce_key(&sender), (expected + 1).to_be_bytes().to_vec());
+        Ok(Receipt { key, hash })
+    }
+
+
+    fn commit(&mut self) -> Result<StateRoot, RepositoryError> {
+        let tree = self.pending_tree();
+        let root = tree.root();
+
+        let mut batch = WriteBatch::new();
+        for (key, value) in self.pending.iter().chain(&self.staged) {
+            batch.put(key.clone(), value.clone());
+        }
+        if Some(self.counter) != *self.last_key {
+            batch.put(counter_key(), self.counter.to_be_bytes());
+        }
+        let mut committed = self.repository.tree.write().unwrap_or_else(PoisonError::into_inner);
+        self.repository.store.write_batch(&batch)?;
+        *committed = tree;
+        drop(committed);
+
+        self.pending.clear();
+        self.staged.clear();
+        *self.last_key = Some(self.counter);
+        Ok(root)
+    }
+}
+
+
+impl Repository {
+    /// Reads `path` at the latest block together with a proof of it against
+    /// the block's state root. A missing or deleted record is proven absent
+    /// rather than reported as an error.
+    pub fn prove(&self, path: &StatePath) -> Result<ProvenRead, RepositoryError> {
+        let tree = self.tree.read().unwrap_or_else(PoisonError::into_inner);
+        let block = self.proof_block(&tree)?;
+        let value = match self.query(path) {
+            Ok(value) => Some(value),
+            Err(RepositoryError::NotFound(_) | RepositoryError::Deleted(_)) => None,
+            Err(e) => return Err(e),
+        };
+        let key = match path {
+            StatePath::Transaction(key) => transaction_key(*key),
+            StatePath::Nonce(sender) => nonce_key(&hex::decode(sender)
+                .map_err(|e| RepositoryError::InvalidSender(e.to_string()))?),
+        };
+        let stored = self.store.get(&key)?;
+
+        Ok(ProvenRead { value, proof: tree.prove(key.as_bytes(), stored.as_deref()), block })
+    }
+
+
+    /// `list_transactions` with a proof of every listed record against the
+    /// latest block's state root, in the same order.
+    pub fn list_proven_transactions(
+        &self,
+        after: Option<i32>,
+        limit: usize,
+        direction: Direction,
+    ) -> Result<(TransactionPage, Vec<StateProof>, ProofBlock), RepositoryError> {
+        let tree = self.tree.read().unwrap_or_else(PoisonError::into_inner);
+        let block = self.proof_block(&tree)?;
+        let page = self.list_transactions(after, limit, direction)?;
+        let proofs = page.transactions.iter()
+            .map(|(key, _)| {
+                let stored = self.get_transaction(key)?;
+                Ok(tree.prove(transaction_key(*key).as_bytes(), Some(&stored)))
+            })
+            .collect::<Result<Vec<StateProof>, RepositoryError>>()?;
+
+        Ok((page, proofs, block))
+    }
+
+
+    /// The latest block, whose state root must be the root of `tree`. Read
+    /// it under the tree lock, which a commit holds while it writes both.
+    fn proof_block(&self, tree: &SparseMerkleTree) -> Result<ProofBlock, RepositoryError> {
+        let block = self.find_block(&BlockId::Latest)?;
+        if block.state_root != tree.root() {
+            return Err(RepositoryError::CorruptBlock {
+                height: block.height,
+                reason: format!("state root {} does not match the committed state {}", block.state_root, tree.root()),
+            });
+        }
+
+        Ok(ProofBlock { height: block.height, hash: block.hash })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::db::DatabaseState;
+    use crate::db::store::Durability;
+    use crate::db::memory::MemoryStore;
+    use crate::db::testing::DirGuard;
+    use crate::ledger::BlockId;
+    use crate::repository::{Repository, RepositoryError};
+    use crate::state::{ProofBlock, StateMachine, StatePath, StateQuery, StateValue};
+    use crate::state::smt::verify_proof;
+    use crate::transaction::StoredRecord;
+    use crate::transaction::testing::signed;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_changes_are_pending_until_commit() {
+        let repository = Repository::new(MemoryStore::new());
+        let sender = signed(1, 0, "").sender;
+
+        let mut state = repository.begin().unwrap();
+        let receipt = state.apply(&signed(1, 0, "a")).unwrap();
+        let pending = state.query(&StatePath::Nonce(sender.clone()));
+        let committed = repository.query(&StatePath::Nonce(sender.clone()));
+        drop(state);
+        let after_drop = repository.query(&StatePath::Transaction(receipt.key));
+
+        let mut state = repository.begin().unwrap();
+        let again = state.apply(&signed(1, 0, "a")).unwrap();
+        let root = state.commit().unwrap();
+        drop(state);
+
+        assert_eq!(receipt.hash, signed(1, 0, "a").hash().unwrap());
+        assert_eq!(pending, Ok(StateValue::Nonce(1)));
+        assert_eq!(committed, Ok(StateValue::Nonce(0)));
+        assert_eq!(after_drop, Err(RepositoryError::NotFound(1)));
+        assert_eq!(again.key, 1);
+        assert_eq!(repository.query(&StatePath::Nonce(sender)), Ok(StateValue::Nonce(1)));
+        assert_eq!(repository.begin().unwrap().root(), root);
+    }
+
+    #[test]
+    fn test_tree_follows_every_write_and_survives_reopen() {
+        let store = Arc::new(MemoryStore::new());
+        let repository = Repository::new(Arc::clone(&store));
+
+        let before_ledger = repository.prove(&StatePath::Transaction(1));
+        let block = repository.append_block(&[signed(1, 0, "a"), signed(2, 0, "b")], 1_000).unwrap().block.unwrap();
+        let reopened = Repository::new(Arc::clone(&store));
+        let reopened_root = reopened.begin().unwrap().root();
+        let read = reopened.prove(&StatePath::Transaction(2)).unwrap();
+        let before_update = reopened.prove(&StatePath::Transaction(1)).unwrap().proof;
+        reopened.update_transaction(&1, 1, &signed(1, 1, "a2")).unwrap();
+        let after_update = reopened.prove(&StatePath::Transaction(1)).unwrap();
+        let raw_remove = reopened.remove_transaction(&2);
+        reopened.delete_transaction(&2, 1, &signed(2, 1, "delete")).unwrap();
+        let removed = reopened.begin().unwrap().root();
+        let deleted = reopened.prove(&StatePath::Transaction(2)).unwrap();
+        let missing = reopened.prove(&StatePath::Transaction(9)).unwrap();
+        let latest = reopened.find_block(&BlockId::Latest).unwrap();
+
+        assert!(matches!(before_ledger, Err(RepositoryError::BlockNotFound(_))));
+        assert_eq!(reopened_root, block.state_root);
+        assert_eq!(read.block, ProofBlock { height: block.height, hash: block.hash });
+        assert_eq!(read.proof.root, block.state_root);
+        assert_eq!(verify_proof(&block.state_root, &read.proof), Ok(()));
+        assert!(matches!(read.value, Some(StateValue::Transaction(ref stored)) if stored.transaction == signed(2, 0, "b")));
+        assert_eq!(after_update.block.height, block.height + 1);
+        assert!(verify_proof(&after_update.proof.root, &before_update).is_err());
+        assert_eq!(verify_proof(&after_update.proof.root, &after_update.proof), Ok(()));
+        assert_eq!(raw_remove, Err(RepositoryError::OutsideLedger(2)));
+        assert_ne!(removed, after_update.proof.root);
+        assert_eq!(Repository::new(store).begin().unwrap().root(), removed);
+        assert_eq!(latest.state_root, removed);
+        assert_eq!(deleted.block, ProofBlock { height: latest.height, hash: latest.hash });
+        assert_eq!(deleted.value, None);
+        assert!(matches!(
+            StoredRecord::decode(&hex::decode(deleted.proof.value.as_ref().unwrap()).unwrap()),
+            Ok(StoredRecord::Tombstone { .. })
+        ));
+        assert_eq!(verify_proof(&latest.state_root, &deleted.proof), Ok(()));
+        assert_eq!(missing.value, None);
+        assert_eq!(missing.proof.value, None);
+        assert_eq!(verify_proof(&latest.state_root, &missing.proof), Ok(()));
+    }
+
+    #[test]
+    fn test_same_transactions_give_the_same_root_on_any_node() {
+        let transactions = [signed(1, 0, "a"), signed(2, 0, "b"), signed(1, 1, "c")];
+        let _guard = DirGuard("./test_db_state_root".to_string());
+
+        let memory = Repository::new(MemoryStore::new());
+        let mut state = memory.begin().unwrap();
+        for transaction in &transactions {
+            state.apply(transaction).unwrap();
+        }
+        let memory_root = state.commit().unwrap();
+        drop(state);
+
+        let leveldb = Repository::new(DatabaseState::open("./test_db_state_root".to_string(), Durability::Sync).unwrap());
+        let mut roots = Vec::new();
+        for transaction in &transactions {
+            let mut state = leveldb.begin().unwrap();
+            state.apply(transaction).unwrap();
+            roots.push(state.commit().unwrap());
+        }
+
+        let reordered = Repository::new(MemoryStore::new());
+        let mut state = reordered.begin().unwrap();
+        state.apply(&transactions[1]).unwrap();
+        state.apply(&transactions[0]).unwrap();
+        state.apply(&transactions[2]).unwrap();
+        let reordered_root = state.commit().unwrap();
+
+        assert_eq!(roots.last(), Some(&memory_root));
+        assert_ne!(roots[0], roots[1]);
+        assert_ne!(reordered_root, memory_root);
+    }
+}
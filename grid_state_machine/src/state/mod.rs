@@ -0,0 +1,76 @@
+pub mod smt;
+
+use crate::db::keys::Keyspace;
+use crate::ledger::Hash;
+use crate::repository::{LegacyRecord, RepositoryError, VersionedTransaction};
+use crate::state::smt::StateProof;
+use crate::transaction::Transaction;
+
+use serde::{Deserialize, Serialize};
+
+/// Keyspaces that make up the state the root commits to. Blocks, the key
+/// counter and other bookkeeping are left out, so the root depends only on
+/// what the applied transactions did.
+pub const STATE_KEYSPACES: [Keyspace; 2] = [Keyspace::Transaction, Keyspace::Account];
+
+/// Root of the sparse Merkle tree over every state entry.
+pub type StateRoot = Hash;
+
+/// What applying one transaction did.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Receipt {
+    /// Key the transaction is stored under.
+    pub key: i32,
+    pub hash: Hash,
+}
+
+/// A readable location in the state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatePath {
+    /// The record stored under a key.
+    Transaction(i32),
+    /// The next nonce of a hex-encoded sender.
+    Nonce(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum StateValue {
+    Transaction(VersionedTransaction),
+    Legacy(LegacyRecord),
+    Nonce(u64),
+}
+
+/// The block a proof was made at. Its `state_root` is the root the proof
+/// leads to, so check the proof against that block's header.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProofBlock {
+    pub height: u64,
+    pub hash: Hash,
+}
+
+/// A read from the state at the latest block, with its proof.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProvenRead {
+    /// `None` when nothing live is at the path. The proof then shows the
+    /// key unset, or holding the tombstone of a deleted record.
+    pub value: Option<StateValue>,
+    pub proof: StateProof,
+    pub block: ProofBlock,
+}
+
+/// Read access to the state.
+pub trait StateQuery {
+    fn query(&self, path: &StatePath) -> Result<StateValue, RepositoryError>;
+}
+
+/// Deterministic transition function over the grid's state. `apply`
+/// changes pending state only, which `query` already sees; `commit` makes
+/// it durable and returns the root. Applying the same transactions to the
+/// same state yields the same root on every node. Pending changes are
+/// discarded if the state machine is dropped before `commit`.
+pub trait StateMachine: StateQuery {
+    fn apply(&mut self, transaction: &Transaction) -> Result<Receipt, RepositoryError>;
+
+    fn commit(&mut self) -> Result<StateRoot, RepositoryError>;
+}
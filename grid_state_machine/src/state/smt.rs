@@ -0,0 +1,337 @@
+use crate::ledger::Hash;
+use crate::state::StateRoot;
+
+use serde::{Serialize, Deserialize};
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Prefix of leaf hashes.
+const LEAF_DOMAIN: &[u8] = b"zgrid:smt:leaf:v1:";
+
+/// Prefix of branch hashes, so a branch never passes for a leaf.
+const NODE_DOMAIN: &[u8] = b"zgrid:smt:node:v1:";
+
+/// Depth of the tree: one level per bit of a path.
+const PATH_BITS: usize = 256;
+
+#[derive(Debug, Clone, Default)]
+enum Node {
+    #[default]
+    Empty,
+    Leaf { path: Hash, value_hash: Hash, hash: Hash },
+    /// Only exists above two or more leaves.
+    Branch { hash: Hash, children: Arc<[Node; 2]> },
+}
+
+impl Node {
+    fn hash(&self) -> Hash {
+        match self {
+            Node::Empty => Hash::ZERO,
+            Node::Leaf { hash, .. } | Node::Branch { hash, .. } => *hash,
+        }
+    }
+
+
+    fn leaf(path: Hash, value_hash: Hash) -> Node {
+        Node::Leaf { path, value_hash, hash: leaf_hash(&path, &value_hash) }
+    }
+
+
+    fn branch(children: [Node; 2]) -> Node {
+        Node::Branch { hash: node_hash(&children[0].hash(), &children[1].hash()), children: Arc::new(children) }
+    }
+
+
+    fn insert(&self, depth: usize, path: Hash, value_hash: Hash) -> Node {
+        match self {
+            Node::Empty => Node::leaf(path, value_hash),
+            Node::Leaf { path: existing, .. } if *existing == path => Node::leaf(path, value_hash),
+            Node::Leaf { path: existing, .. } => split(depth, (*existing, self.clone()), (path, Node::leaf(path, value_hash))),
+            Node::Branch { children, .. } => {
+                let side = bit(&path, depth);
+                let mut children = (**children).clone();
+                children[side] = children[side].insert(depth + 1, path, value_hash);
+                Node::branch(children)
+            }
+        }
+    }
+
+
+    fn remove(&self, depth: usize, path: &Hash) -> Node {
+        match self {
+            Node::Empty => Node::Empty,
+            Node::Leaf { path: existing, .. } if existing == path => Node::Empty,
+            Node::Leaf { .. } => self.clone(),
+            Node::Branch { children, .. } => {
+                let side = bit(path, depth);
+                let mut children = (**children).clone();
+                children[side] = children[side].remove(depth + 1, path);
+                match children {
+                    [Node::Empty, Node::Empty] => Node::Empty,
+                    [leaf @ Node::Leaf { .. }, Node::Empty] | [Node::Empty, leaf @ Node::Leaf { .. }] => leaf,
+                    children => Node::branch(children),
+                }
+            }
+        }
+    }
+}
+
+
+/// A compact sparse Merkle tree over 256-bit paths. A subtree holding no
+/// leaf hashes to zero, one holding a single leaf hashes to that leaf, and
+/// any other to `NODE_DOMAIN || left || right`. The root therefore depends
+/// only on the set of leaves, never on the order they were written in.
+/// Nodes are shared between versions, so a clone is cheap and updating it
+/// leaves the original as it was.
+#[derive(Debug, Clone, Default)]
+pub struct SparseMerkleTree {
+    root: Node,
+}
+
+impl SparseMerkleTree {
+    pub fn new() -> Self {
+        SparseMerkleTree::default()
+    }
+
+
+    pub fn root(&self) -> StateRoot {
+        self.root.hash()
+    }
+
+
+    /// Sets the value stored under `key`.
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        self.root = self.root.insert(0, key_path(key), value_hash(value));
+    }
+
+
+    pub fn remove(&mut self, key: &[u8]) {
+        self.root = self.root.remove(0, &key_path(key));
+    }
+
+
+    /// Proof that `key` holds `value`, or is unset when `value` is `None`.
+    /// The caller reads `value` from the store the tree mirrors; a proof
+    /// for the wrong value does not verify.
+    pub fn prove(&self, key: &[u8], value: Option<&[u8]>) -> StateProof {
+        let path = key_path(key);
+        let mut siblings = Vec::new();
+        let mut node = &self.root;
+        let mut depth = 0;
+
+        while let Node::Branch { children, .. } = node {
+            let side = bit(&path, depth);
+            siblings.push(children[1 - side].hash());
+            node = &children[side];
+            depth += 1;
+        }
+        let neighbour = match node {
+            Node::Leaf { path: other, value_hash, .. } if value.is_none() && *other != path => {
+                Some(ProofLeaf { path: *other, value_hash: *value_hash })
+            }
+            _ => None,
+        };
+
+        StateProof {
+            root: self.root(),
+            key: hex::encode(key),
+            value: value.map(hex::encode),
+            siblings,
+            neighbour,
+        }
+    }
+}
+
+impl<K: AsRef<[u8]>, V: AsRef<[u8]>> FromIterator<(K, V)> for SparseMerkleTree {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        let mut tree = SparseMerkleTree::new();
+        for (key, value) in entries {
+            tree.insert(key.as_ref(), value.as_ref());
+        }
+        tree
+    }
+}
+
+
+/// Merkle proof of one store key against a state root. Check it with
+/// `verify_proof`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateProof {
+    /// Root the proof was made against.
+    pub root: StateRoot,
+    /// Store key, hex-encoded.
+    pub key: String,
+    /// Bytes stored under `key`, hex-encoded; `None` proves the key unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Hashes of the siblings along the key's path, from the root down.
+    pub siblings: Vec<Hash>,
+    /// For an unset key, the one leaf found where the key would be.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub neighbour: Option<ProofLeaf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProofLeaf {
+    pub path: Hash,
+    pub value_hash: Hash,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProofError {
+    Malformed(String),
+    /// The proof leads to `computed` rather than the trusted root.
+    RootMismatch { expected: Hash, computed: Hash },
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::Malformed(reason) => write!(f, "Proof: Malformed: {}", reason),
+            ProofError::RootMismatch { expected, computed } => write!(
+                f, "Proof: Leads to root {}, expected {}", computed, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+
+/// Checks `proof` against a root the caller trusts, such as the
+/// `state_root` of a block, rather than the root the proof carries. `Ok`
+/// means the state at `root` holds `proof.value` under `proof.key`, or
+/// nothing when `value` is `None`.
+pub fn verify_proof(root: &StateRoot, proof: &StateProof) -> Result<(), ProofError> {
+    let key = hex::decode(&proof.key)
+        .map_err(|e| ProofError::Malformed(format!("key: {}", e)))?;
+    let path = key_path(&key);
+    if proof.siblings.len() >= PATH_BITS {
+        return Err(ProofError::Malformed(format!("{} siblings", proof.siblings.len())));
+    }
+
+    let mut hash = match (&proof.value, &proof.neighbour) {
+        (Some(value), None) => {
+            let value = hex::decode(value)
+                .map_err(|e| ProofError::Malformed(format!("value: {}", e)))?;
+            leaf_hash(&path, &value_hash(&value))
+        }
+        (None, None) => Hash::ZERO,
+        (None, Some(neighbour)) => {
+            let depth = proof.siblings.len();
+            if neighbour.path == path || (0..depth).any(|i| bit(&neighbour.path, i) != bit(&path, i)) {
+                return Err(ProofError::Malformed("neighbour is not beside the key".to_string()));
+            }
+            leaf_hash(&neighbour.path, &neighbour.value_hash)
+        }
+        (Some(_), Some(_)) => return Err(ProofError::Malformed("value and neighbour both set".to_string())),
+    };
+
+    for (depth, sibling) in proof.siblings.iter().enumerate().rev() {
+        hash = match bit(&path, depth) {
+            0 => node_hash(&hash, sibling),
+            _ => node_hash(sibling, &hash),
+        };
+    }
+
+    if hash == *root {
+        Ok(())
+    } else {
+        Err(ProofError::RootMismatch { expected: *root, computed: hash })
+    }
+}
+
+
+/// Joins two leaves with different paths under the branches their paths share.
+fn split(depth: usize, (a_path, a): (Hash, Node), (b_path, b): (Hash, Node)) -> Node {
+    let (a_side, b_side) = (bit(&a_path, depth), bit(&b_path, depth));
+    let mut children = [Node::Empty, Node::Empty];
+    if a_side == b_side {
+        children[a_side] = split(depth + 1, (a_path, a), (b_path, b));
+    } else {
+        children[a_side] = a;
+        children[b_side] = b;
+    }
+    Node::branch(children)
+}
+
+
+fn key_path(key: &[u8]) -> Hash {
+    Hash::digest(&[key])
+}
+
+
+fn value_hash(value: &[u8]) -> Hash {
+    Hash::digest(&[value])
+}
+
+
+fn leaf_hash(path: &Hash, value_hash: &Hash) -> Hash {
+    Hash::digest(&[LEAF_DOMAIN, &path.0, &value_hash.0])
+}
+
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    Hash::digest(&[NODE_DOMAIN, &left.0, &right.0])
+}
+
+
+/// Bit `depth` of `path`, most significant first.
+fn bit(path: &Hash, depth: usize) -> usize {
+    ((path.0[depth / 8] >> (7 - depth % 8)) & 1) as usize
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::ledger::Hash;
+    use crate::state::smt::{verify_proof, ProofError, SparseMerkleTree};
+
+    fn entries(count: u32) -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..count).map(|i| (format!("key {}", i).into_bytes(), format!("value {}", i).into_bytes())).collect()
+    }
+
+    #[test]
+    fn test_root_depends_only_on_the_entries() {
+        let forward: SparseMerkleTree = entries(50).into_iter().collect();
+        let backward: SparseMerkleTree = entries(50).into_iter().rev().collect();
+        let mut removed = forward.clone();
+        for (key, _) in entries(50).iter().skip(20) {
+            removed.remove(key);
+        }
+        let mut updated = forward.clone();
+        updated.insert(b"key 3", b"changed");
+
+        assert_eq!(SparseMerkleTree::new().root(), Hash::ZERO);
+        assert_eq!(forward.root(), backward.root());
+        assert_eq!(removed.root(), entries(20).into_iter().collect::<SparseMerkleTree>().root());
+        assert_ne!(updated.root(), forward.root());
+        assert_ne!(forward.root(), entries(49).into_iter().collect::<SparseMerkleTree>().root());
+    }
+
+    #[test]
+    fn test_proofs_verify_only_against_their_root() {
+        let tree: SparseMerkleTree = entries(50).into_iter().collect();
+        let root = tree.root();
+
+        for (key, value) in entries(50) {
+            assert_eq!(verify_proof(&root, &tree.prove(&key, Some(&value))), Ok(()));
+        }
+        let unset: Vec<_> = (0..20).map(|i| tree.prove(format!("unset {}", i).as_bytes(), None)).collect();
+        for proof in &unset {
+            assert_eq!(verify_proof(&root, proof), Ok(()));
+        }
+
+        let wrong_value = tree.prove(b"key 1", Some(b"value 2"));
+        let absent_but_set = tree.prove(b"key 1", None);
+        let mut moved_neighbour = unset.iter().find(|proof| proof.neighbour.is_some()).unwrap().clone();
+        moved_neighbour.neighbour.as_mut().unwrap().path = Hash::digest(&[b"key 1"]);
+        assert!(unset.iter().any(|proof| proof.neighbour.is_none()));
+        assert!(matches!(verify_proof(&root, &wrong_value), Err(ProofError::RootMismatch { .. })));
+        assert!(verify_proof(&root, &absent_but_set).is_err());
+        assert!(verify_proof(&root, &moved_neighbour).is_err());
+        assert!(verify_proof(&Hash::ZERO, &tree.prove(b"key 1", Some(b"value 1"))).is_err());
+        assert_eq!(verify_proof(&Hash::ZERO, &SparseMerkleTree::new().prove(b"key", None)), Ok(()));
+    }
+}
@@ -0,0 +1,327 @@
+use crate::ledger::Hash;
+
+use libp2p_identity::{Keypair, PublicKey, SigningError};
+use serde::{Serialize, Deserialize};
+
+use std::fmt;
+
+/// Prefix of every signed encoding, so a transaction signature can never be
+/// replayed as a signature over some other grid structure. v1 predates fees
+/// and still covers fee-free transactions; v2 adds the fee.
+const SIGNING_DOMAIN_V1: &[u8] = b"zgrid:transaction:v1:";
+const SIGNING_DOMAIN_V2: &[u8] = b"zgrid:transaction:v2:";
+
+/// Transaction as accepted by the API, signed by its sender. `sender` is the
+/// hex protobuf encoding of an ed25519 libp2p public key, the same key type
+/// `grid_gossip` nodes use as their identity, and `signature` is the hex
+/// ed25519 signature over `signing_bytes()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub sender: String,
+    pub nonce: u64,
+    /// What the sender offers for inclusion; the mempool applies higher
+    /// fees first.
+    #[serde(default)]
+    pub fee: u64,
+    /// Milliseconds since the Unix epoch, as claimed by the sender.
+    pub timestamp: u64,
+    pub payload: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionError {
+    /// `sender` is not a hex protobuf-encoded public key.
+    Sender(String),
+    /// `sender` decodes, but not to an ed25519 key.
+    KeyType,
+    /// `signature` is not hex or does not cover the transaction.
+    Signature,
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::Sender(reason) => write!(f, "Transaction: Invalid sender key: {}", reason),
+            TransactionError::KeyType => write!(f, "Transaction: Sender key is not ed25519"),
+            TransactionError::Signature => write!(f, "Transaction: Signature does not match"),
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
+impl Transaction {
+    /// Builds a transaction from `keypair` and signs it. `keypair` must be
+    /// ed25519, e.g. a `grid_node.key` loaded with `from_protobuf_encoding`.
+    pub fn sign(keypair: &Keypair, nonce: u64, fee: u64, timestamp: u64, payload: String) -> Result<Self, SigningError> {
+        let sender = keypair.public().encode_protobuf();
+        let signature = keypair.sign(&signing_bytes(&sender, nonce, fee, timestamp, &payload))?;
+
+        Ok(Transaction {
+            sender: hex::encode(sender),
+            nonce,
+            fee,
+            timestamp,
+            payload,
+            signature: hex::encode(signature),
+        })
+    }
+
+
+    pub fn sender_key(&self) -> Result<PublicKey, TransactionError> {
+        let bytes = hex::decode(&self.sender)
+            .map_err(|e| TransactionError::Sender(e.to_string()))?;
+        let public_key = PublicKey::try_decode_protobuf(&bytes)
+            .map_err(|e| TransactionError::Sender(e.to_string()))?;
+        if public_key.clone().try_into_ed25519().is_err() {
+            return Err(TransactionError::KeyType);
+        }
+        Ok(public_key)
+    }
+
+
+    /// Checks that `signature` is the sender's signature over this transaction.
+    pub fn verify(&self) -> Result<(), TransactionError> {
+        let public_key = self.sender_key()?;
+        let signature = hex::decode(&self.signature)
+            .map_err(|_| TransactionError::Signature)?;
+        let message = signing_bytes(&public_key.encode_protobuf(), self.nonce, self.fee, self.timestamp, &self.payload);
+
+        if public_key.verify(&message, &signature) {
+            Ok(())
+        } else {
+            Err(TransactionError::Signature)
+        }
+    }
+
+
+    /// Identifies the transaction in blocks: SHA-256 over its signing bytes
+    /// followed by the signature, so it names one signed transaction.
+    pub fn hash(&self) -> Result<Hash, TransactionError> {
+        let sender = hex::decode(&self.sender)
+            .map_err(|e| TransactionError::Sender(e.to_string()))?;
+        let signature = hex::decode(&self.signature)
+            .map_err(|_| TransactionError::Signature)?;
+        let message = signing_bytes(&sender, self.nonce, self.fee, self.timestamp, &self.payload);
+
+        Ok(Hash::digest(&[&message, &signature]))
+    }
+}
+
+
+/// Canonical encoding covered by the signature: the signing domain, then the
+/// protobuf sender key and the payload, each prefixed with its length as a
+/// big-endian u32, with the big-endian u64 nonce, fee and timestamp between
+/// them. A fee-free transaction uses the v1 encoding, which has no fee
+/// field, so signatures made before fees existed still verify.
+fn signing_bytes(sender: &[u8], nonce: u64, fee: u64, timestamp: u64, payload: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(SIGNING_DOMAIN_V2.len() + 32 + sender.len() + payload.len());
+    bytes.extend_from_slice(if fee == 0 { SIGNING_DOMAIN_V1 } else { SIGNING_DOMAIN_V2 });
+    bytes.extend_from_slice(&(sender.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(sender);
+    bytes.extend_from_slice(&nonce.to_be_bytes());
+    if fee != 0 {
+        bytes.extend_from_slice(&fee.to_be_bytes());
+    }
+    bytes.extend_from_slice(&timestamp.to_be_bytes());
+    bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(payload.as_bytes());
+    bytes
+}
+
+
+/// Stored form of a transaction. Every update bumps `version`; a delete
+/// leaves a tombstone so the key is never reused. Records written before
+/// versioning carry no `version` and decode as version 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoredRecord {
+    Live { version: u64, transaction: Transaction },
+    /// A record from before signed transactions (`{"data":..}`). It has no
+    /// sender, so it can be read but never updated or deleted.
+    Legacy { version: u64, data: String },
+    Tombstone { version: u64 },
+}
+
+/// On-disk JSON layout of `StoredRecord`.
+#[derive(Serialize, Deserialize)]
+struct RawRecord {
+    #[serde(default = "first_version")]
+    version: u64,
+    #[serde(flatten)]
+    transaction: Option<Transaction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    tombstone: bool,
+}
+
+fn first_version() -> u64 {
+    1
+}
+
+impl StoredRecord {
+    pub fn version(&self) -> u64 {
+        match self {
+            StoredRecord::Live { version, .. }
+            | StoredRecord::Legacy { version, .. }
+            | StoredRecord::Tombstone { version } => *version,
+        }
+    }
+
+
+    pub fn encode(&self) -> Result<Vec<u8>, serde_json::Error> {
+        let raw = match self {
+            StoredRecord::Live { version, transaction } => RawRecord {
+                version: *version,
+                transaction: Some(transaction.clone()),
+                data: None,
+                tombstone: false,
+            },
+            StoredRecord::Legacy { version, data } => RawRecord {
+                version: *version,
+                transaction: None,
+                data: Some(data.clone()),
+                tombstone: false,
+            },
+            StoredRecord::Tombstone { version } => RawRecord {
+                version: *version,
+                transaction: None,
+                data: None,
+                tombstone: true,
+            },
+        };
+        serde_json::to_vec(&raw)
+    }
+
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        use serde::de::Error;
+
+        let raw: RawRecord = serde_json::from_slice(bytes)?;
+        match (raw.transaction, raw.data, raw.tombstone) {
+            (Some(transaction), None, false) => Ok(StoredRecord::Live { version: raw.version, transaction }),
+            (None, Some(data), false) => Ok(StoredRecord::Legacy { version: raw.version, data }),
+            (None, None, true) => Ok(StoredRecord::Tombstone { version: raw.version }),
+            (Some(_), Some(_), _) => Err(serde_json::Error::custom("record is both signed and legacy")),
+            (_, _, true) => Err(serde_json::Error::custom("tombstone carries transaction data")),
+            (None, None, false) => Err(serde_json::Error::custom("record has no transaction data")),
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod testing {
+    use crate::transaction::Transaction;
+    use libp2p_identity::Keypair;
+
+    /// Deterministic ed25519 keypair; different seeds give different senders.
+    pub fn keypair(seed: u8) -> Keypair {
+        Keypair::ed25519_from_bytes([seed; 32]).unwrap()
+    }
+
+
+    /// A transaction carrying `payload` with `nonce` and no fee, signed by
+    /// `keypair(sender)`.
+    pub fn signed(sender: u8, nonce: u64, payload: &str) -> Transaction {
+        with_fee(sender, nonce, 0, payload)
+    }
+
+
+    pub fn with_fee(sender: u8, nonce: u64, fee: u64, payload: &str) -> Transaction {
+        Transaction::sign(&keypair(sender), nonce, fee, 1_700_000_000_000, payload.to_string()).unwrap()
+    }
+
+
+    /// The first transaction of `keypair(1)`, carrying `payload`.
+    pub fn transaction(payload: &str) -> Transaction {
+        signed(1, 0, payload)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::transaction::{StoredRecord, Transaction, TransactionError};
+    use crate::transaction::testing::{keypair, transaction};
+
+    #[test]
+    fn test_stored_record_round_trip() {
+        let live = StoredRecord::Live { version: 2, transaction: transaction("meow") };
+        let tombstone = StoredRecord::Tombstone { version: 3 };
+        let legacy = StoredRecord::Legacy { version: 2, data: "older".to_string() };
+        let unversioned = serde_json::to_vec(&transaction("old")).unwrap();
+
+        assert!(live.encode().unwrap().starts_with(br#"{"version":2,"sender":""#));
+        assert_eq!(tombstone.encode().unwrap(), br#"{"version":3,"tombstone":true}"#.to_vec());
+        assert_eq!(StoredRecord::decode(&live.encode().unwrap()).unwrap(), live);
+        assert_eq!(StoredRecord::decode(&tombstone.encode().unwrap()).unwrap(), tombstone);
+        assert_eq!(StoredRecord::decode(&unversioned).unwrap().version(), 1);
+        assert_eq!(
+            StoredRecord::decode(br#"{"data":"old"}"#).unwrap(),
+            StoredRecord::Legacy { version: 1, data: "old".to_string() }
+        );
+        assert_eq!(StoredRecord::decode(&legacy.encode().unwrap()).unwrap(), legacy);
+        assert!(StoredRecord::decode(br#"{"version":4}"#).is_err());
+        assert!(StoredRecord::decode(b"Hello, Meow!").is_err());
+    }
+
+    #[test]
+    fn test_signatures_cover_every_field() {
+        let signed = Transaction::sign(&keypair(1), 7, 3, 1_700_000_000_000, "transfer 369".to_string()).unwrap();
+
+        let mut payload = signed.clone();
+        payload.payload = "transfer 963".to_string();
+        let mut nonce = signed.clone();
+        nonce.nonce = 8;
+        let mut fee = signed.clone();
+        fee.fee = 30;
+        let mut timestamp = signed.clone();
+        timestamp.timestamp += 1;
+        let mut sender = signed.clone();
+        sender.sender = Transaction::sign(&keypair(2), 7, 3, 1_700_000_000_000, "x".to_string()).unwrap().sender;
+        let mut garbage = signed.clone();
+        garbage.sender = "Hello, Meow!".to_string();
+
+        assert_eq!(signed.verify(), Ok(()));
+        assert_eq!(signed.sender_key().unwrap(), keypair(1).public());
+        assert_eq!(payload.verify(), Err(TransactionError::Signature));
+        assert_eq!(nonce.verify(), Err(TransactionError::Signature));
+        assert_eq!(fee.verify(), Err(TransactionError::Signature));
+        assert_eq!(timestamp.verify(), Err(TransactionError::Signature));
+        assert_eq!(sender.verify(), Err(TransactionError::Signature));
+        assert!(matches!(garbage.verify(), Err(TransactionError::Sender(_))));
+    }
+
+
+    #[test]
+    fn test_fee_free_signatures_use_the_v1_encoding() {
+        let sender = keypair(1).public().encode_protobuf();
+        let mut v1 = b"zgrid:transaction:v1:".to_vec();
+        v1.extend_from_slice(&(sender.len() as u32).to_be_bytes());
+        v1.extend_from_slice(&sender);
+        v1.extend_from_slice(&7u64.to_be_bytes());
+        v1.extend_from_slice(&1_700_000_000_000u64.to_be_bytes());
+        v1.extend_from_slice(&12u32.to_be_bytes());
+        v1.extend_from_slice(b"transfer 369");
+        let old = Transaction {
+            sender: hex::encode(&sender),
+            nonce: 7,
+            fee: 0,
+            timestamp: 1_700_000_000_000,
+            payload: "transfer 369".to_string(),
+            signature: hex::encode(keypair(1).sign(&v1).unwrap()),
+        };
+
+        let mut fee_added = old.clone();
+        fee_added.fee = 1;
+        let mut fee_dropped = Transaction::sign(&keypair(1), 7, 1, 1_700_000_000_000, "transfer 369".to_string()).unwrap();
+        fee_dropped.fee = 0;
+
+        assert_eq!(old.verify(), Ok(()));
+        assert_eq!(Transaction::sign(&keypair(1), 7, 0, 1_700_000_000_000, "transfer 369".to_string()).unwrap(), old);
+        assert_eq!(fee_added.verify(), Err(TransactionError::Signature));
+        assert_eq!(fee_dropped.verify(), Err(TransactionError::Signature));
+    }
+}